@@ -1,18 +1,90 @@
-use std::io::{self, Write};
-
 use actix_web::Scope;
 use actix_web::{get, post, web, HttpResponse};
-use serde::Deserialize;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
 use tokio::sync::{oneshot, Mutex};
 
 use agent_api::types::task::*;
 
 use crate::api::TaskOutcome;
+use crate::artifacts::{ArtifactError, MAX_ARTIFACT_SIZE};
 use crate::context::Context;
+use crate::event::EventKind;
+use crate::prompt;
 
 #[derive(Deserialize)]
 pub struct InquiryPayload {
     pub inquiry: String,
+    /// Describes the expected shape of the answer.
+    /// If absent, the answer is returned as a bare JSON string.
+    #[serde(default)]
+    pub answer: Option<AnswerSchema>,
+}
+
+/// The kind of answer an agent expects to an inquiry.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnswerSchema {
+    /// Pick one (or, if `multiple` is set, any number) of the given options.
+    Choice {
+        options: Vec<String>,
+        #[serde(default)]
+        multiple: bool,
+        #[serde(default)]
+        default: Option<usize>,
+    },
+    /// Answer with yes or no.
+    Boolean {
+        #[serde(default)]
+        default: Option<bool>,
+    },
+    /// Answer with free text.
+    Text {
+        #[serde(default)]
+        default: Option<String>,
+    },
+}
+
+/// Structured answer returned for inquiries that specify an [`AnswerSchema`].
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Answer {
+    Choice {
+        index: usize,
+        value: String,
+    },
+    MultipleChoice {
+        indices: Vec<usize>,
+        values: Vec<String>,
+    },
+    Boolean {
+        value: bool,
+    },
+    Text {
+        value: String,
+    },
+}
+
+/// Progress update reported by the agent.
+/// Fields that are absent leave the previously reported value unchanged.
+#[derive(Deserialize)]
+pub struct ProgressPayload {
+    /// The step the agent is currently working on.
+    #[serde(default)]
+    pub step: Option<String>,
+    /// The steps the agent plans to take.
+    #[serde(default)]
+    pub plan: Option<Vec<String>>,
+    /// Estimated overall progress in percent.
+    #[serde(default)]
+    pub percentage: Option<f32>,
+}
+
+#[derive(Deserialize)]
+pub struct ArtifactQuery {
+    /// File name under which the artifact is stored.
+    pub filename: String,
 }
 
 pub fn scope() -> Scope {
@@ -21,6 +93,8 @@ pub fn scope() -> Scope {
         .service(task_complete)
         .service(task_fail)
         .service(inquiry)
+        .service(progress)
+        .service(upload_artifact)
 }
 
 #[get("/task")]
@@ -39,12 +113,21 @@ pub async fn task_info(ctx: web::Data<Context>) -> HttpResponse {
 
 #[post("/task/complete")]
 pub async fn task_complete(
+    ctx: web::Data<Context>,
     body: web::Json<TaskComplete>,
     shutdown_tx: web::Data<Mutex<Option<oneshot::Sender<TaskOutcome>>>>,
 ) -> HttpResponse {
     let body = body.into_inner();
+    ctx.status.finish();
+    ctx.metrics
+        .task_outcomes
+        .with_label_values(&["completed"])
+        .inc();
     println!("Task completed");
     println!("{}", body.description);
+    ctx.events.emit(EventKind::TaskCompleted {
+        description: body.description,
+    });
 
     let tx = shutdown_tx
         .lock()
@@ -59,11 +142,21 @@ pub async fn task_complete(
 
 #[post("/task/fail")]
 pub async fn task_fail(
+    ctx: web::Data<Context>,
     body: web::Json<TaskFailure>,
     shutdown_tx: web::Data<Mutex<Option<oneshot::Sender<TaskOutcome>>>>,
 ) -> HttpResponse {
+    let body = body.into_inner();
+    ctx.status.finish();
+    ctx.metrics
+        .task_outcomes
+        .with_label_values(&["failed"])
+        .inc();
     println!("Task failed");
     println!("{}", body.description);
+    ctx.events.emit(EventKind::TaskFailed {
+        description: body.description,
+    });
 
     let tx = shutdown_tx
         .lock()
@@ -75,27 +168,143 @@ pub async fn task_fail(
 
     HttpResponse::Ok().finish()
 }
+
 /// Send an inquiry to the user and await its answer.
 /// Agents use this endpoint to request clarification on their tasks.
 #[post("/inquiry")]
-pub async fn inquiry(request: web::Json<InquiryPayload>) -> HttpResponse {
-    let question = request.inquiry.clone();
+pub async fn inquiry(ctx: web::Data<Context>, request: web::Json<InquiryPayload>) -> HttpResponse {
+    let InquiryPayload { inquiry, answer } = request.into_inner();
 
-    println!("Agent is asking: {question}");
+    if let Some(AnswerSchema::Choice { options, .. }) = &answer {
+        if options.is_empty() {
+            return HttpResponse::BadRequest().body("Choice inquiry without options");
+        }
+    }
 
-    let answer = (tokio::task::spawn_blocking(move || {
-        print!("Your answer: ");
-        io::stdout().flush().ok();
+    ctx.metrics.inquiries.inc();
+    ctx.events.emit(EventKind::InquiryAsked {
+        inquiry: inquiry.clone(),
+    });
 
-        let mut input = String::new();
-        if io::stdin().read_line(&mut input).is_ok() {
-            input.trim().to_string()
-        } else {
-            String::new()
-        }
+    let status = ctx.status.clone();
+    let result = tokio::task::spawn_blocking(move || -> std::io::Result<serde_json::Value> {
+        let _suspended = status.suspend();
+        println!("Agent is asking: {inquiry}");
+        let Some(schema) = answer else {
+            return Ok(json!(prompt::text(None).unwrap_or_default()));
+        };
+        let answer = match schema {
+            AnswerSchema::Choice {
+                options,
+                multiple: false,
+                default,
+            } => {
+                let default = default.filter(|&i| i < options.len());
+                let index = prompt::select(&options, default)?;
+                Answer::Choice {
+                    index,
+                    value: options[index].clone(),
+                }
+            }
+            AnswerSchema::Choice {
+                options,
+                multiple: true,
+                default,
+            } => {
+                let default = default.filter(|&i| i < options.len());
+                let indices = prompt::multi_select(&options, default)?;
+                let values = indices.iter().map(|&i| options[i].clone()).collect();
+                Answer::MultipleChoice { indices, values }
+            }
+            AnswerSchema::Boolean { default } => Answer::Boolean {
+                value: prompt::confirm(default)?,
+            },
+            AnswerSchema::Text { default } => Answer::Text {
+                value: prompt::text(default.as_deref())?,
+            },
+        };
+        Ok(json!(answer))
     })
-    .await)
-        .unwrap_or_default();
+    .await;
+
+    match result
+        .map_err(std::io::Error::other)
+        .and_then(|answer| answer)
+    {
+        Ok(answer) => {
+            ctx.events.emit(EventKind::InquiryAnswered {
+                answer: answer.clone(),
+            });
+            HttpResponse::Ok().json(answer)
+        }
+        Err(err) => {
+            HttpResponse::InternalServerError().body(format!("Failed to read answer: {err}"))
+        }
+    }
+}
+
+/// Report the agent's progress to the user.
+#[post("/progress")]
+pub async fn progress(ctx: web::Data<Context>, body: web::Json<ProgressPayload>) -> HttpResponse {
+    let ProgressPayload {
+        step,
+        plan,
+        percentage,
+    } = body.into_inner();
+
+    ctx.status.update(|progress| {
+        if let Some(step) = step {
+            progress.step = Some(step);
+        }
+        if let Some(plan) = plan {
+            progress.plan = plan;
+        }
+        if let Some(percentage) = percentage {
+            progress.percentage = Some(percentage.clamp(0.0, 100.0));
+        }
+    });
+
+    HttpResponse::Ok().finish()
+}
+
+/// Store the request body as an artifact of the run.
+/// Artifacts are kept with the run instead of being committed to the repository.
+#[post("/artifacts")]
+pub async fn upload_artifact(
+    ctx: web::Data<Context>,
+    query: web::Query<ArtifactQuery>,
+    mut payload: web::Payload,
+) -> HttpResponse {
+    let mut data = web::BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(err) => return HttpResponse::BadRequest().body(err.to_string()),
+        };
+        if data.len() + chunk.len() > MAX_ARTIFACT_SIZE {
+            return HttpResponse::PayloadTooLarge().body(ArtifactError::QuotaExceeded.to_string());
+        }
+        data.extend_from_slice(&chunk);
+    }
+
+    let artifacts = ctx.artifacts.clone();
+    let filename = query.into_inner().filename;
+    let result = web::block(move || artifacts.store(&filename, &data)).await;
 
-    HttpResponse::Ok().json(answer)
+    match result {
+        Ok(Ok(_)) => HttpResponse::Created().finish(),
+        Ok(Err(err @ ArtifactError::InvalidName)) => {
+            HttpResponse::BadRequest().body(err.to_string())
+        }
+        Ok(Err(err @ ArtifactError::AlreadyExists)) => {
+            HttpResponse::Conflict().body(err.to_string())
+        }
+        Ok(Err(err @ ArtifactError::QuotaExceeded)) => {
+            HttpResponse::PayloadTooLarge().body(err.to_string())
+        }
+        Ok(Err(err @ ArtifactError::Io(_))) => {
+            HttpResponse::InternalServerError().body(err.to_string())
+        }
+        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+    }
 }
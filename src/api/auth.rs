@@ -5,7 +5,7 @@ use actix_web_httpauth::extractors::bearer::BearerAuth;
 
 use crate::context::Context;
 
-/// Validator function for Bearer authentication
+/// Validator function for Bearer authentication of the agent
 pub async fn bearer_auth_validator(
     req: ServiceRequest,
     credentials: BearerAuth,
@@ -20,3 +20,19 @@ pub async fn bearer_auth_validator(
         Err((ErrorUnauthorized("Invalid API key"), req))
     }
 }
+
+/// Validator function for Bearer authentication of run observers
+pub async fn observer_auth_validator(
+    req: ServiceRequest,
+    credentials: BearerAuth,
+) -> Result<ServiceRequest, (Error, ServiceRequest)> {
+    let ctx = req
+        .app_data::<web::Data<Context>>()
+        .expect("Context not found in app data");
+
+    if credentials.token() == ctx.observer_api_key {
+        Ok(req)
+    } else {
+        Err((ErrorUnauthorized("Invalid observer token"), req))
+    }
+}
@@ -1,58 +1,683 @@
-use std::sync::Arc;
+use std::task::Poll;
+use std::time::Instant;
 
-use actix_web::{web, Error, HttpRequest, Scope};
-use serde_json::Value;
+use actix_web::body::{BodyStream, BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{CONTENT_TYPE, RETRY_AFTER};
+use actix_web::http::StatusCode;
+use actix_web::middleware::Next;
+use actix_web::{error, web, Error, HttpMessage as _, HttpRequest, HttpResponse, Scope};
+use futures::StreamExt as _;
+use serde_json::{json, Value};
 
 use llm_proxy::{CompletionRequest, ForwardConfig, ProxyConfig};
 
+use super::context_window;
+use super::policy;
+use super::stream::{self, StreamAssembler};
+use super::tools;
+use crate::cache::ResponseCache;
 use crate::context::Context;
+use crate::event::EventKind;
+use crate::providers::{Provider, Upstream};
+use crate::ratelimit::{self, Permit};
 
 pub fn scope() -> Scope {
     llm_proxy::scope(TheProxyConfig {})
 }
 
+/// A chat completion request, parsed once by [`parse_request`] and passed to the other
+/// middlewares in the request extensions. Middlewares take it out of the extensions to
+/// inspect or change it and put it back before calling the next one.
+struct ChatRequest {
+    /// The request body, including the changes of the middlewares so far.
+    body: Value,
+    /// The model as requested by the agent, or the default model.
+    model: String,
+    /// The provider the request is routed to.
+    provider: &'static dyn Provider,
+    /// The upstream model (`provider/model`) the request is routed to.
+    upstream_model: String,
+    /// Where and how the request is forwarded to the provider.
+    upstream: Upstream,
+}
+
+impl ChatRequest {
+    /// Parse a request body. Fails with an error response if it isn't JSON, names no model
+    /// and no default model is configured, or the provider can't serve the model.
+    fn parse(ctx: &Context, body: &[u8]) -> Result<Self, HttpResponse> {
+        let Ok(body) = serde_json::from_slice::<Value>(body) else {
+            return Err(invalid_request("The request body is not valid JSON", None));
+        };
+        let Some(model) = body["model"]
+            .as_str()
+            .or(ctx.llm_router_table.default_model())
+        else {
+            return Err(invalid_request("Missing model in request", Some("model")));
+        };
+        let model = model.to_owned();
+        let (model_name, details) = ctx.llm_router_table.details_for_model(&model);
+        let upstream = details
+            .provider
+            .chat_upstream(details, &model_name)
+            .map_err(|err| invalid_request(&err.to_string(), Some("model")))?;
+        Ok(Self {
+            upstream_model: format!("{}/{model_name}", details.provider.id()),
+            upstream,
+            provider: details.provider,
+            body,
+            model,
+        })
+    }
+}
+
+/// Outermost middleware parsing chat completion requests for the inner middlewares, see
+/// [`ChatRequest`]. It also records latency metrics for the chat proxy.
+pub async fn parse_request(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let ctx = context(&req);
+    let body = read_payload(&mut req).await?;
+    let timer = ctx.metrics.chat_request_duration.start_timer();
+
+    let res = match ChatRequest::parse(&ctx, &body) {
+        Ok(chat) => {
+            req.extensions_mut().insert(chat);
+            next.call(req)
+                .await
+                .map(ServiceResponse::map_into_boxed_body)
+        }
+        Err(response) => Ok(req.into_response(response)),
+    };
+
+    timer.observe_duration();
+    res
+}
+
+/// Innermost middleware forwarding chat completion requests, as changed by the other
+/// middlewares, to the provider. Responses are relayed as they arrive. Non-streamed
+/// interactions are recorded here, streamed ones by [`stream_usage`]. Requests answered
+/// without reaching the provider, e.g. from the response cache, don't count towards the
+/// run's usage. Every request is answered here, so none reaches the proxy.
+pub async fn send_request(
+    req: ServiceRequest,
+    _next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let ctx = context(&req);
+    let chat = req.extensions_mut().remove::<ChatRequest>();
+    let Some(chat) = chat else {
+        return Err(error::ErrorInternalServerError(
+            "Chat completion request was not parsed",
+        ));
+    };
+
+    let (model_name, _) = ctx.llm_router_table.details_for_model(&chat.model);
+    ctx.metrics
+        .chat_requests
+        .with_label_values(&[chat.provider.id(), model_name.as_str()])
+        .inc();
+
+    let started = Instant::now();
+    let response = chat.upstream.post(&chat.body).await;
+    ctx.usage
+        .record_request(&chat.upstream_model, started.elapsed());
+    let response = match response {
+        Ok(response) => response,
+        Err(err) => {
+            ctx.metrics
+                .chat_responses
+                .with_label_values(&[chat.provider.id(), &model_name, "502"])
+                .inc();
+            record_interaction(&ctx, Some(&chat.model), None);
+            return Err(error::ErrorBadGateway(format!(
+                "Failed to reach {}: {err}",
+                chat.provider.display_name()
+            )));
+        }
+    };
+
+    let status =
+        StatusCode::from_u16(response.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+    ctx.metrics
+        .chat_responses
+        .with_label_values(&[chat.provider.id(), &model_name, status.as_str()])
+        .inc();
+    let mut builder = HttpResponse::build(status);
+    for name in [CONTENT_TYPE, RETRY_AFTER] {
+        if let Some(value) = response.headers().get(name.as_str()) {
+            builder.insert_header((name, value.as_bytes()));
+        }
+    }
+
+    if chat.body["stream"].as_bool() == Some(true) {
+        let body = futures::stream::unfold(Some(response), |response| async move {
+            let mut response = response?;
+            match response.chunk().await {
+                Ok(Some(chunk)) => Some((Ok(chunk), Some(response))),
+                Ok(None) => None,
+                Err(err) => Some((Err(err), None)),
+            }
+        });
+        return Ok(req.into_response(builder.streaming(body)));
+    }
+
+    // Non-streamed responses are single JSON documents, so buffering them doesn't delay
+    // the agent.
+    let body = response.bytes().await.map_err(error::ErrorBadGateway)?;
+    let response = serde_json::from_slice::<Value>(&body)
+        .ok()
+        .filter(|_| status.is_success());
+    log::debug!("Request: {}\n\nResponse: {response:?}", chat.body);
+    record_interaction(&ctx, Some(&chat.model), response.as_ref());
+    Ok(req.into_response(builder.body(body)))
+}
+
+/// Provides the route of the chat completions endpoint. Requests are answered by the
+/// middlewares, ending with [`send_request`], so they never reach the proxy itself.
 #[derive(Clone)]
 struct TheProxyConfig {}
 
 impl ProxyConfig for TheProxyConfig {
-    type Context = Arc<Context>;
-
-    async fn extract_context(&self, req: &HttpRequest) -> Result<Self::Context, Error> {
-        let ctx = req
-            .app_data::<web::Data<Context>>()
-            .expect("Context not found in app data");
-        let ctx = ctx.clone().into_inner();
+    type Context = ();
 
-        Ok(ctx)
+    async fn extract_context(&self, _req: &HttpRequest) -> Result<Self::Context, Error> {
+        Ok(())
     }
 
     async fn forward(
         &self,
-        ctx: &Self::Context,
-        req: &CompletionRequest,
+        _ctx: &Self::Context,
+        _req: &CompletionRequest,
     ) -> Result<ForwardConfig, Error> {
-        let Some(model) = req.model.as_ref() else {
-            return Err(actix_web::error::ErrorBadRequest(
-                "Missing model in request",
-            ));
-        };
-        let (model_name, details) = &ctx.llm_router_table.details_for_model(model);
-
-        Ok(ForwardConfig {
-            api_key: details.api_key.clone(),
-            target_url: details.api_chat_completions_endpoint.clone(),
-            model: Some(model_name.clone()),
-        })
+        unreachable!("Chat completion requests are forwarded by `send_request`")
     }
 
     async fn inspect_interaction(
         &self,
         _ctx: &Self::Context,
-        request: &CompletionRequest,
-        response: Option<Value>,
+        _request: &CompletionRequest,
+        _response: Option<Value>,
     ) {
-        // For now we just log raw request and response
-        // Later we will need to come up with a proper feedback mechanism
-        println!("Request: {request:?}\n\nResponse: {response:?}");
+        unreachable!("Chat completion requests are forwarded by `send_request`")
     }
 }
+
+/// Middleware applying the configured request policy. Requests for denied models are
+/// rejected, all others get the policy's system instructions and parameter limits. This runs
+/// before the response cache, so that cached responses also obey the policy.
+pub async fn apply_policy(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let ctx = context(&req);
+    let Some(mut chat) = req.extensions_mut().remove::<ChatRequest>() else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+
+    if ctx.policy.denies(&chat.upstream_model) {
+        let response = HttpResponse::Forbidden().json(policy::denied_body(&chat.upstream_model));
+        return Ok(req.into_response(response));
+    }
+    policy::apply(
+        &ctx.policy,
+        &mut chat.body,
+        chat.provider.max_tokens_param(),
+    );
+
+    req.extensions_mut().insert(chat);
+    Ok(next.call(req).await?.map_into_boxed_body())
+}
+
+/// Middleware for streamed chat completions. Asks the provider to include token usage in the
+/// stream, relays the stream unchanged while reassembling it, and records the interaction
+/// when the stream ends. Usage is estimated if the provider doesn't report it.
+pub async fn stream_usage(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let ctx = context(&req);
+    let Some(mut chat) = req.extensions_mut().remove::<ChatRequest>() else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+    if chat.body["stream"].as_bool() != Some(true) {
+        req.extensions_mut().insert(chat);
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    }
+
+    if chat.provider.supports_stream_usage()
+        && chat.body["stream_options"]["include_usage"].is_null()
+    {
+        chat.body["stream_options"]["include_usage"] = Value::Bool(true);
+    }
+    let request = chat.body.clone();
+    let model = Some(chat.model.clone());
+    req.extensions_mut().insert(chat);
+
+    let res = next.call(req).await?;
+    if !is_event_stream(&res) {
+        record_interaction(&ctx, model.as_deref(), None);
+        return Ok(res.map_into_boxed_body());
+    }
+
+    let mut recorder = StreamRecorder {
+        ctx,
+        model,
+        request,
+        assembler: Some(StreamAssembler::default()),
+    };
+    Ok(res.map_body(move |_, body| {
+        let mut body = Box::pin(body);
+        let stream = futures::stream::poll_fn(move |cx| {
+            let poll = body.as_mut().poll_next(cx);
+            match &poll {
+                Poll::Ready(Some(Ok(chunk))) => recorder.push(chunk),
+                Poll::Ready(None) => recorder.finish(),
+                _ => {}
+            }
+            poll
+        });
+        BoxBody::new(BodyStream::new(stream))
+    }))
+}
+
+/// Reassembles a streamed response for [`stream_usage`] and records the interaction when the
+/// stream ends, or when it's dropped because the agent disconnected before the end.
+struct StreamRecorder {
+    ctx: web::Data<Context>,
+    model: Option<String>,
+    request: Value,
+    /// `None` once the interaction is recorded.
+    assembler: Option<StreamAssembler>,
+}
+
+impl StreamRecorder {
+    fn push(&mut self, chunk: &[u8]) {
+        if let Some(assembler) = &mut self.assembler {
+            assembler.push(chunk);
+        }
+    }
+
+    fn finish(&mut self) {
+        let Some(assembler) = self.assembler.take() else {
+            return;
+        };
+        let mut response = assembler.finish();
+        if response.get("usage").is_none() {
+            response["usage"] = stream::estimate_usage(&self.request, &response);
+        }
+        log::debug!("Request: {}\n\nResponse: {response}", self.request);
+        record_interaction(&self.ctx, self.model.as_deref(), Some(&response));
+    }
+}
+
+impl Drop for StreamRecorder {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// Middleware answering identical non-streamed chat completion requests from the response
+/// cache, if enabled. Requests are identical if the same body, as changed by the outer
+/// middlewares, would be forwarded to the same endpoint. Cached responses don't count
+/// towards the rate limits.
+pub async fn response_cache(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let ctx = context(&req);
+    let Some(cache) = ctx.response_cache.clone() else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+    let key = req
+        .extensions()
+        .get::<ChatRequest>()
+        .filter(|chat| chat.body["stream"].as_bool() != Some(true))
+        .map(|chat| ResponseCache::key(&chat.body, &chat.upstream.url, &chat.upstream.model));
+    let Some(key) = key else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+
+    if let Some(response) = cache.get(&key) {
+        ctx.metrics.response_cache.with_label_values(&["hit"]).inc();
+        let model = req
+            .extensions()
+            .get::<ChatRequest>()
+            .map(|chat| chat.model.clone());
+        record_cached_interaction(
+            &ctx,
+            model.as_deref(),
+            &serde_json::from_slice(&response).unwrap_or_default(),
+        );
+        let response = HttpResponse::Ok()
+            .content_type("application/json")
+            .body(response);
+        return Ok(req.into_response(response));
+    }
+    ctx.metrics
+        .response_cache
+        .with_label_values(&["miss"])
+        .inc();
+
+    let res = next.call(req).await?;
+    if !res.status().is_success() {
+        return Ok(res.map_into_boxed_body());
+    }
+    // Non-streamed responses are single JSON documents, so buffering them doesn't delay
+    // the agent.
+    let (req, res) = res.into_parts();
+    let (res, body) = res.into_parts();
+    let body = read_body(body).await?;
+    if let Err(err) = cache.put(&key, &body) {
+        log::warn!("Failed to cache response: {err}");
+    }
+    Ok(ServiceResponse::new(req, res.set_body(BoxBody::new(body))))
+}
+
+/// Middleware enforcing the client-side rate limits of the provider a request is routed to.
+/// Requests wait until they fit the limits, and a `retry-after` header in a rate limit
+/// response holds back further requests to the provider.
+pub async fn rate_limit(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let ctx = context(&req);
+    let request = req
+        .extensions()
+        .get::<ChatRequest>()
+        .map(|chat| (chat.provider, stream::estimate_prompt_tokens(&chat.body)));
+    let Some((provider, tokens)) = request else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+    let Some(permit) = acquire_rate_limit(&ctx, provider.id(), tokens).await else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+
+    let res = next.call(req).await?;
+    let retry_after = res
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok());
+    respect_retry_after(&ctx, provider.id(), res.status(), retry_after);
+
+    // Keep the concurrency slot until the response, which may be streamed, is complete.
+    Ok(res.map_body(move |_, body| {
+        let mut body = Box::pin(body);
+        let mut permit = Some(permit);
+        let stream = futures::stream::poll_fn(move |cx| {
+            let poll = body.as_mut().poll_next(cx);
+            if let Poll::Ready(None) = poll {
+                permit.take();
+            }
+            poll
+        });
+        BoxBody::new(BodyStream::new(stream))
+    }))
+}
+
+/// Wait until a request to the provider using about `tokens` tokens fits the provider's rate
+/// limits, showing the wait in the status line. Returns `None` if the provider has no limits.
+pub(super) async fn acquire_rate_limit(
+    ctx: &Context,
+    provider: &str,
+    tokens: u64,
+) -> Option<Permit> {
+    let limiter = ctx.rate_limits.get(provider)?;
+    let mut waited = false;
+    let permit = limiter
+        .acquire(tokens, |wait| {
+            waited = true;
+            log::debug!("Waiting {wait:?} for the {provider} rate limit");
+            let until = (!wait.is_zero()).then(|| Instant::now() + wait);
+            ctx.status.update(|progress| {
+                progress.rate_limited = Some((provider.to_owned(), until));
+            });
+        })
+        .await;
+    if waited {
+        ctx.status.update(|progress| progress.rate_limited = None);
+    }
+    Some(permit)
+}
+
+/// Hold back further requests to the provider if it answered with a rate limit error and a
+/// `retry-after` header.
+pub(super) fn respect_retry_after(
+    ctx: &Context,
+    provider: &str,
+    status: StatusCode,
+    retry_after: Option<&str>,
+) {
+    if status != StatusCode::TOO_MANY_REQUESTS {
+        return;
+    }
+    let Some(limiter) = ctx.rate_limits.get(provider) else {
+        return;
+    };
+    if let Some(wait) = retry_after.and_then(ratelimit::parse_retry_after) {
+        limiter.block_for(wait);
+    }
+}
+
+/// Middleware keeping requests within the context window of the model, if known. Requests
+/// that don't fit are shortened with the configured strategy, or rejected with a
+/// `context_length_exceeded` error the agent can act on.
+pub async fn fit_context_window(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let ctx = context(&req);
+    let Some(mut chat) = req.extensions_mut().remove::<ChatRequest>() else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+
+    if let Some(&window) = ctx.context_windows.get(&chat.upstream_model) {
+        match context_window::fit(&mut chat.body, window, ctx.context_strategy) {
+            Ok(false) => {}
+            Ok(true) => log::info!(
+                "Shortened a request to {} to fit its context window",
+                chat.upstream_model
+            ),
+            Err(exceeded) => {
+                let response = HttpResponse::BadRequest()
+                    .json(context_window::error_body(&exceeded, &chat.upstream_model));
+                return Ok(req.into_response(response));
+            }
+        }
+    }
+
+    req.extensions_mut().insert(chat);
+    Ok(next.call(req).await?.map_into_boxed_body())
+}
+
+/// Middleware rewriting tool calling requests into what the provider supports and responses,
+/// streamed or not, into the strict OpenAI tool calling shape. Requests using tool
+/// features the provider can't support are rejected with a descriptive error.
+pub async fn normalize_tools(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let Some(mut chat) = req.extensions_mut().remove::<ChatRequest>() else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+
+    if let Err(unsupported) = tools::normalize_request(&mut chat.body, chat.provider) {
+        let response = HttpResponse::BadRequest().json(tools::error_body(&unsupported));
+        return Ok(req.into_response(response));
+    }
+    let streamed = chat.body["stream"].as_bool() == Some(true);
+    req.extensions_mut().insert(chat);
+
+    let res = next.call(req).await?;
+    if !res.status().is_success() {
+        return Ok(res.map_into_boxed_body());
+    }
+    if streamed {
+        if !is_event_stream(&res) {
+            return Ok(res.map_into_boxed_body());
+        }
+        return Ok(res.map_body(|_, body| {
+            let mut body = Box::pin(body);
+            let mut normalizer = Some(tools::StreamNormalizer::default());
+            let stream = futures::stream::poll_fn(move |cx| loop {
+                let Some(active) = &mut normalizer else {
+                    return Poll::Ready(None);
+                };
+                let normalized = match body.as_mut().poll_next(cx) {
+                    Poll::Ready(Some(Ok(chunk))) => active.push(&chunk),
+                    Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(err))),
+                    Poll::Ready(None) => {
+                        let rest = active.finish();
+                        normalizer = None;
+                        rest
+                    }
+                    Poll::Pending => return Poll::Pending,
+                };
+                // Lines may span chunks, so there may be nothing to pass on yet.
+                if !normalized.is_empty() {
+                    return Poll::Ready(Some(Ok(web::Bytes::from(normalized))));
+                }
+            });
+            BoxBody::new(BodyStream::new(stream))
+        }));
+    }
+    let (req, res) = res.into_parts();
+    let (res, body) = res.into_parts();
+    let body = read_body(body).await?;
+    let body = match serde_json::from_slice::<Value>(&body) {
+        Ok(mut response) => {
+            if tools::normalize_response(&mut response) {
+                web::Bytes::from(serde_json::to_vec(&response)?)
+            } else {
+                body
+            }
+        }
+        Err(_) => body,
+    };
+    Ok(ServiceResponse::new(req, res.set_body(BoxBody::new(body))))
+}
+
+/// The context of the API server.
+fn context(req: &ServiceRequest) -> web::Data<Context> {
+    req.app_data::<web::Data<Context>>()
+        .expect("Context not found in app data")
+        .clone()
+}
+
+/// An OpenAI-style error response for a request the agent needs to fix.
+fn invalid_request(message: &str, param: Option<&str>) -> HttpResponse {
+    HttpResponse::BadRequest().json(json!({
+        "error": {
+            "message": message,
+            "type": "invalid_request_error",
+            "param": param,
+        }
+    }))
+}
+
+/// Whether the response is a stream of server-sent events.
+fn is_event_stream<B>(res: &ServiceResponse<B>) -> bool {
+    res.headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/event-stream"))
+}
+
+/// Read the complete request body.
+async fn read_payload(req: &mut ServiceRequest) -> Result<web::Bytes, Error> {
+    let mut payload = req.take_payload();
+    let mut body = web::BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        body.extend_from_slice(&chunk?);
+    }
+    Ok(body.freeze())
+}
+
+/// Read a complete response body.
+async fn read_body(body: impl MessageBody) -> Result<web::Bytes, Error> {
+    actix_web::body::to_bytes(body).await.map_err(|err| {
+        let err: Box<dyn std::error::Error> = err.into();
+        error::ErrorBadGateway(err.to_string())
+    })
+}
+
+/// Update the status line, metrics and event stream with a completed LLM interaction.
+/// `response` is `None` if the interaction failed.
+pub(super) fn record_interaction(
+    ctx: &Context,
+    requested_model: Option<&str>,
+    response: Option<&Value>,
+) {
+    let tokens = response
+        .and_then(|response| response["usage"]["total_tokens"].as_u64())
+        .unwrap_or(0);
+    let model = interaction_model(ctx, requested_model);
+    ctx.status.update(|progress| {
+        progress.tokens += tokens;
+        progress.last_model_call = Some((model.clone(), Instant::now()));
+    });
+
+    let prompt_tokens = response.and_then(|response| response["usage"]["prompt_tokens"].as_u64());
+    let completion_tokens =
+        response.and_then(|response| response["usage"]["completion_tokens"].as_u64());
+
+    let provider = ctx.llm_router_table.provider_for_model(&model);
+    let (model_name, _) = ctx.llm_router_table.details_for_model(&model);
+    ctx.metrics
+        .tokens
+        .with_label_values(&[provider, model_name.as_str(), "in"])
+        .inc_by(prompt_tokens.unwrap_or(0));
+    ctx.metrics
+        .tokens
+        .with_label_values(&[provider, model_name.as_str(), "out"])
+        .inc_by(completion_tokens.unwrap_or(0));
+
+    if let Some(limiter) = ctx.rate_limits.get(provider) {
+        limiter.add_tokens(completion_tokens.unwrap_or(0));
+    }
+    ctx.usage.record_tokens(
+        &format!("{provider}/{model_name}"),
+        prompt_tokens.unwrap_or(0),
+        completion_tokens.unwrap_or(0),
+    );
+
+    ctx.events.emit(EventKind::LlmInteraction {
+        model,
+        prompt_tokens,
+        completion_tokens,
+        finish_reason: response.and_then(|response| {
+            response["choices"][0]["finish_reason"]
+                .as_str()
+                .map(str::to_owned)
+        }),
+        failed: response.is_none(),
+        cached: false,
+    });
+}
+
+/// Update the status line and event stream with an interaction answered from the response
+/// cache. It used no tokens, so it isn't counted in the metrics, rate limits and usage.
+fn record_cached_interaction(ctx: &Context, requested_model: Option<&str>, response: &Value) {
+    let model = interaction_model(ctx, requested_model);
+    ctx.status.update(|progress| {
+        progress.last_model_call = Some((model.clone(), Instant::now()));
+    });
+    ctx.events.emit(EventKind::LlmInteraction {
+        model,
+        prompt_tokens: None,
+        completion_tokens: None,
+        finish_reason: response["choices"][0]["finish_reason"]
+            .as_str()
+            .map(str::to_owned),
+        failed: false,
+        cached: true,
+    });
+}
+
+/// The model an interaction is attributed to: the requested one or the default model.
+fn interaction_model(ctx: &Context, requested_model: Option<&str>) -> String {
+    requested_model
+        .or(ctx.llm_router_table.default_model())
+        .unwrap_or_default()
+        .to_owned()
+}
@@ -0,0 +1,223 @@
+//! Keeping chat completion requests within the context window of the model.
+//!
+//! Token counts are estimates, since the tokenizers of most models aren't available.
+
+use std::cmp::Reverse;
+
+use serde_json::{json, Value};
+
+use super::stream::{estimate_message_tokens, estimate_prompt_tokens};
+use crate::config::ContextStrategy;
+
+/// Replacement for tool outputs dropped to fit the context window.
+const DROPPED_OUTPUT: &str = "[Output removed to fit the context window]";
+
+/// Characters kept at the start and at the end of a shortened message.
+const MIN_KEPT_CHARS: usize = 256;
+
+/// Characters taken up by the marker inserted into shortened messages.
+const MARKER_CHARS: usize = 80;
+
+/// A request that doesn't fit the context window of the model.
+pub struct Exceeded {
+    /// Estimated prompt tokens of the request as sent by the agent.
+    pub prompt_tokens: u64,
+    /// Tokens reserved for the completion with `max_tokens` or `max_completion_tokens`.
+    pub completion_tokens: u64,
+    pub context_window: u64,
+}
+
+/// Make the request fit a context window of `context_window` tokens, leaving room for the
+/// requested completion tokens. Returns whether the request was changed.
+pub fn fit(
+    request: &mut Value,
+    context_window: u64,
+    strategy: ContextStrategy,
+) -> Result<bool, Exceeded> {
+    let completion_tokens = request["max_completion_tokens"]
+        .as_u64()
+        .or(request["max_tokens"].as_u64())
+        .unwrap_or(0);
+    let limit = context_window.saturating_sub(completion_tokens);
+    let prompt_tokens = estimate_prompt_tokens(request);
+    if prompt_tokens <= limit {
+        return Ok(false);
+    }
+    let exceeded = Exceeded {
+        prompt_tokens,
+        completion_tokens,
+        context_window,
+    };
+
+    let Some(messages) = request.get_mut("messages").and_then(Value::as_array_mut) else {
+        return Err(exceeded);
+    };
+    let mut tokens = prompt_tokens;
+    if strategy != ContextStrategy::Error {
+        tokens = drop_tool_outputs(messages, tokens, limit);
+    }
+    if strategy == ContextStrategy::Truncate && tokens > limit {
+        tokens = truncate_messages(messages, tokens, limit);
+    }
+    if tokens > limit {
+        return Err(exceeded);
+    }
+    Ok(true)
+}
+
+/// Replace tool outputs, oldest first, with a placeholder until the request fits `limit`.
+/// Outputs the model hasn't responded to yet are kept. Returns the remaining tokens.
+fn drop_tool_outputs(messages: &mut [Value], mut tokens: u64, limit: u64) -> u64 {
+    let answered = messages
+        .iter()
+        .rposition(|message| message["role"] == "assistant")
+        .unwrap_or(0);
+    for message in &mut messages[..answered] {
+        if tokens <= limit {
+            break;
+        }
+        if message["role"] != "tool" || message["content"] == DROPPED_OUTPUT {
+            continue;
+        }
+        let before = estimate_message_tokens(message);
+        message["content"] = json!(DROPPED_OUTPUT);
+        tokens = tokens.saturating_sub(before) + estimate_message_tokens(message);
+    }
+    tokens
+}
+
+/// Cut the middle out of the longest messages until the request fits `limit`. System
+/// messages and the last message are kept intact. Returns the remaining tokens.
+fn truncate_messages(messages: &mut [Value], mut tokens: u64, limit: u64) -> u64 {
+    let Some((_, earlier)) = messages.split_last_mut() else {
+        return tokens;
+    };
+    let length = |message: &Value| message["content"].as_str().map_or(0, |c| c.chars().count());
+    let mut candidates: Vec<usize> = (0..earlier.len())
+        .filter(|&i| earlier[i]["content"].is_string() && earlier[i]["role"] != "system")
+        .collect();
+    candidates.sort_by_key(|&i| Reverse(length(&earlier[i])));
+
+    for i in candidates {
+        if tokens <= limit {
+            break;
+        }
+        let message = &mut earlier[i];
+        let chars = length(message);
+        // The estimate assumes four characters per token.
+        let excess = (tokens - limit) as usize * 4;
+        let keep = chars
+            .saturating_sub(excess + MARKER_CHARS)
+            .max(2 * MIN_KEPT_CHARS);
+        if chars <= keep + MARKER_CHARS {
+            continue;
+        }
+        let before = estimate_message_tokens(message);
+        let shortened = cut_middle(message["content"].as_str().unwrap_or_default(), keep);
+        message["content"] = json!(shortened);
+        tokens = tokens.saturating_sub(before) + estimate_message_tokens(message);
+    }
+    tokens
+}
+
+/// Keep `keep` characters of the text, half from the start and half from the end.
+fn cut_middle(text: &str, keep: usize) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let half = keep / 2;
+    let head: String = chars[..half].iter().collect();
+    let tail: String = chars[chars.len() - half..].iter().collect();
+    let removed = chars.len() - 2 * half;
+    format!("{head}\n[… {removed} characters removed to fit the context window …]\n{tail}")
+}
+
+/// An OpenAI-style error body for a request exceeding the context window of `model`.
+pub fn error_body(exceeded: &Exceeded, model: &str) -> Value {
+    json!({
+        "error": {
+            "message": format!(
+                "This request needs about {} prompt tokens plus {} completion tokens, which \
+                 exceeds the {}-token context window of {model}. Shorten the conversation, \
+                 e.g. by removing old tool outputs, and try again.",
+                exceeded.prompt_tokens, exceeded.completion_tokens, exceeded.context_window
+            ),
+            "type": "invalid_request_error",
+            "param": "messages",
+            "code": "context_length_exceeded",
+            "prompt_tokens": exceeded.prompt_tokens,
+            "completion_tokens": exceeded.completion_tokens,
+            "context_window": exceeded.context_window,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(tool_output: &str, last: &str) -> Value {
+        json!({
+            "messages": [
+                { "role": "system", "content": "You are a coding agent." },
+                { "role": "user", "content": "List the files." },
+                { "role": "assistant", "tool_calls": [] },
+                { "role": "tool", "content": tool_output },
+                { "role": "assistant", "content": "Done." },
+                { "role": "user", "content": last },
+            ],
+        })
+    }
+
+    #[test]
+    fn keeps_requests_that_fit() {
+        let mut request = request("file.txt", "Thanks.");
+        let original = request.clone();
+
+        assert!(matches!(
+            fit(&mut request, 1000, ContextStrategy::Truncate),
+            Ok(false)
+        ));
+        assert_eq!(request, original);
+    }
+
+    #[test]
+    fn rejects_requests_that_exceed_the_window() {
+        let mut request = request(&"x".repeat(4000), "Thanks.");
+        request["max_tokens"] = json!(100);
+        let original = request.clone();
+
+        let Err(exceeded) = fit(&mut request, 500, ContextStrategy::Error) else {
+            panic!("Request should exceed the context window");
+        };
+        assert_eq!(exceeded.completion_tokens, 100);
+        assert_eq!(exceeded.context_window, 500);
+        assert!(exceeded.prompt_tokens > 1000);
+        assert_eq!(request, original);
+    }
+
+    #[test]
+    fn drops_answered_tool_outputs() {
+        let mut request = request(&"x".repeat(4000), "Thanks.");
+
+        assert!(matches!(
+            fit(&mut request, 500, ContextStrategy::DropToolOutputs),
+            Ok(true)
+        ));
+        assert_eq!(request["messages"][3]["content"], DROPPED_OUTPUT);
+        assert!(estimate_prompt_tokens(&request) <= 500);
+    }
+
+    #[test]
+    fn truncates_long_messages() {
+        let mut request = request("file.txt", &"y".repeat(400));
+        request["messages"][1]["content"] = json!("z".repeat(8000));
+
+        assert!(matches!(
+            fit(&mut request, 1000, ContextStrategy::Truncate),
+            Ok(true)
+        ));
+        assert!(estimate_prompt_tokens(&request) <= 1000);
+        let shortened = request["messages"][1]["content"].as_str().unwrap();
+        assert!(shortened.starts_with("zzz") && shortened.ends_with("zzz"));
+        assert_eq!(request["messages"][5]["content"], "y".repeat(400));
+    }
+}
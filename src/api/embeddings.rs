@@ -0,0 +1,98 @@
+//! Proxy for OpenAI-compatible embeddings requests, so that agents can create embeddings
+//! with the user's keys just like chat completions.
+
+use std::time::Instant;
+
+use actix_web::http::header::RETRY_AFTER;
+use actix_web::http::StatusCode;
+use actix_web::{error, post, web, Error, HttpResponse};
+use serde_json::{json, Value};
+
+use super::chat::{acquire_rate_limit, record_interaction, respect_retry_after};
+use super::policy;
+use super::stream::estimate_tokens;
+use crate::context::Context;
+
+/// Model alias used for requests that don't specify a model.
+const DEFAULT_ALIAS: &str = "embeddings";
+
+#[post("/embeddings")]
+pub async fn create(
+    ctx: web::Data<Context>,
+    body: web::Json<Value>,
+) -> Result<HttpResponse, Error> {
+    let request = body.into_inner();
+    let model = request["model"]
+        .as_str()
+        .unwrap_or(DEFAULT_ALIAS)
+        .to_owned();
+    let (model_name, details) = ctx.llm_router_table.details_for_model(&model);
+    let upstream_model = format!("{}/{model_name}", details.provider.id());
+    if ctx.policy.denies(&upstream_model) {
+        return Ok(HttpResponse::Forbidden().json(policy::denied_body(&upstream_model)));
+    }
+    let upstream = match details.provider.embeddings_upstream(details, &model_name) {
+        Ok(upstream) => upstream,
+        Err(err) => {
+            return Ok(HttpResponse::BadRequest().json(json!({
+                "error": {
+                    "message": err.to_string(),
+                    "type": "invalid_request_error",
+                    "param": "model",
+                }
+            })));
+        }
+    };
+
+    let provider = details.provider.id();
+    let _permit = acquire_rate_limit(&ctx, provider, input_tokens(&request["input"])).await;
+    let started = Instant::now();
+    let response = upstream.post(&request).await;
+    ctx.usage.record_request(&upstream_model, started.elapsed());
+    let response = match response {
+        Ok(response) => response,
+        Err(err) => {
+            record_interaction(&ctx, Some(&model), None);
+            return Err(error::ErrorBadGateway(format!(
+                "Failed to reach {}: {err}",
+                details.provider.display_name()
+            )));
+        }
+    };
+
+    let status =
+        StatusCode::from_u16(response.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+    let retry_after = response
+        .headers()
+        .get(RETRY_AFTER.as_str())
+        .and_then(|value| value.to_str().ok());
+    respect_retry_after(&ctx, provider, status, retry_after);
+    let body = response.bytes().await.map_err(error::ErrorBadGateway)?;
+    // Embedding vectors are large and of no interest in the event stream, only the usage is.
+    let usage = serde_json::from_slice::<Value>(&body)
+        .ok()
+        .filter(|_| status.is_success())
+        .map(|response| json!({ "usage": response["usage"] }));
+    record_interaction(&ctx, Some(&model), usage.as_ref());
+
+    Ok(HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body))
+}
+
+/// Estimate the tokens of the input of an embeddings request, given as a text, a list of
+/// texts or already tokenized.
+fn input_tokens(input: &Value) -> u64 {
+    match input {
+        Value::String(text) => estimate_tokens(text),
+        Value::Array(items) => items
+            .iter()
+            .map(|item| match item {
+                Value::Array(tokens) => tokens.len() as u64,
+                Value::Number(_) => 1,
+                item => input_tokens(item),
+            })
+            .sum(),
+        _ => 0,
+    }
+}
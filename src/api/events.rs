@@ -0,0 +1,31 @@
+use actix_web::{get, web, HttpResponse};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::context::Context;
+
+/// Stream run events to observers as server-sent events.
+#[get("")]
+pub async fn stream(ctx: web::Data<Context>) -> HttpResponse {
+    let rx = ctx.events.subscribe();
+
+    let events = futures::stream::unfold(rx, |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) => {
+                    let data = serde_json::to_string(&event).expect("Failed to serialize event");
+                    let message = format!("event: {}\ndata: {data}\n\n", event.kind.name());
+                    return Some((Ok::<_, actix_web::Error>(web::Bytes::from(message)), rx));
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    log::warn!("Event observer lagging behind, skipped {skipped} events");
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(events)
+}
@@ -1,5 +1,9 @@
-use actix_web::dev::ServiceRequest;
+use std::task::Poll;
+
+use actix_web::body::{BodyStream, BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
 use actix_web::error::{Error, ErrorUnauthorized};
+use actix_web::middleware::Next;
 use actix_web::web;
 use actix_web::HttpMessage;
 use actix_web_httpauth::extractors::basic::BasicAuth;
@@ -7,6 +11,7 @@ use actix_web_httpauth::extractors::basic::BasicAuth;
 use git_proxy::{ForwardToLocal, ProxyBehaivor};
 
 use crate::context::Context;
+use crate::event::EventKind;
 
 /// Validator function for Basic authentication
 pub async fn basic_auth_validator(
@@ -20,6 +25,17 @@ pub async fn basic_auth_validator(
     let password = credentials.password().unwrap_or("");
 
     if password == ctx.agent_api_key {
+        if req.path().ends_with("/git-upload-pack") {
+            ctx.metrics
+                .git_operations
+                .with_label_values(&["fetch"])
+                .inc();
+        } else if req.path().ends_with("/git-receive-pack") {
+            ctx.metrics
+                .git_operations
+                .with_label_values(&["push"])
+                .inc();
+        }
         req.extensions_mut().insert(ProxyBehaivor {
             allowed_ref: format!("refs/heads/{}", ctx.git_branch.clone()),
             forward: ForwardToLocal {
@@ -32,3 +48,37 @@ pub async fn basic_auth_validator(
         Err((ErrorUnauthorized("Invalid username or password"), req))
     }
 }
+
+/// Middleware emitting an event once the agent's push has been received, i.e. when a
+/// successful `git-receive-pack` response is complete.
+pub async fn track_push(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let is_push = req.path().ends_with("/git-receive-pack");
+    let ctx = req
+        .app_data::<web::Data<Context>>()
+        .expect("Context not found in app data")
+        .clone();
+
+    let res = next.call(req).await?;
+    if !is_push || !res.status().is_success() {
+        return Ok(res.map_into_boxed_body());
+    }
+    Ok(res.map_body(move |_, body| {
+        let mut body = Box::pin(body);
+        let mut ctx = Some(ctx);
+        let stream = futures::stream::poll_fn(move |cx| {
+            let poll = body.as_mut().poll_next(cx);
+            if let Poll::Ready(None) = poll {
+                if let Some(ctx) = ctx.take() {
+                    ctx.events.emit(EventKind::GitPushReceived {
+                        branch: ctx.git_branch.clone(),
+                    });
+                }
+            }
+            poll
+        });
+        BoxBody::new(BodyStream::new(stream))
+    }))
+}
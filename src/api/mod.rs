@@ -1,5 +1,6 @@
 use std::net::TcpListener;
 
+use actix_web::middleware::from_fn;
 use actix_web::{middleware, web, App, HttpServer};
 use actix_web_httpauth::middleware::HttpAuthentication;
 use tokio::sync::{oneshot, Mutex};
@@ -9,8 +10,17 @@ use crate::context::Context;
 mod agent;
 mod auth;
 mod chat;
+mod context_window;
+mod embeddings;
+mod events;
 mod git;
+mod policy;
 mod probes;
+mod stream;
+mod tools;
+
+/// Seconds to wait for pending responses, such as event streams, when the task is done.
+const SHUTDOWN_TIMEOUT_SECS: u64 = 5;
 
 #[derive(Debug, PartialEq)]
 pub enum TaskOutcome {
@@ -19,25 +29,48 @@ pub enum TaskOutcome {
 }
 
 pub async fn run_server(listener: TcpListener, ctx: Context) -> anyhow::Result<TaskOutcome> {
+    let events = ctx.events.clone();
     let ctx = web::Data::new(ctx);
     let (shutdown_tx, shutdown_rx) = oneshot::channel::<TaskOutcome>();
     let shutdown_tx = web::Data::new(Mutex::new(Some(shutdown_tx)));
 
     let server = HttpServer::new(move || {
         let bearer_auth = HttpAuthentication::bearer(auth::bearer_auth_validator);
+        let observer_auth = HttpAuthentication::bearer(auth::observer_auth_validator);
 
         App::new()
             .app_data(ctx.clone())
             .app_data(shutdown_tx.clone())
-            .service(git_proxy::scope(
-                "/api/agent/git",
-                git::basic_auth_validator,
-            ))
+            .service(
+                git_proxy::scope("/api/agent/git", git::basic_auth_validator)
+                    .wrap(from_fn(git::track_push)),
+            )
             .service(
                 web::scope("/api")
                     .wrap(bearer_auth)
                     .service(agent::scope())
-                    .service(chat::scope()),
+                    .service(embeddings::create)
+                    .service(
+                        chat::scope()
+                            .wrap(from_fn(chat::send_request))
+                            .wrap(from_fn(chat::rate_limit))
+                            .wrap(from_fn(chat::response_cache))
+                            .wrap(from_fn(chat::normalize_tools))
+                            .wrap(from_fn(chat::stream_usage))
+                            .wrap(from_fn(chat::fit_context_window))
+                            .wrap(from_fn(chat::apply_policy))
+                            .wrap(from_fn(chat::parse_request)),
+                    ),
+            )
+            .service(
+                web::scope("/events")
+                    .wrap(observer_auth.clone())
+                    .service(events::stream),
+            )
+            .service(
+                web::scope("/metrics")
+                    .wrap(observer_auth)
+                    .service(probes::metrics),
             )
             .service(probes::readiness)
             .service(probes::healthz)
@@ -50,12 +83,24 @@ pub async fn run_server(listener: TcpListener, ctx: Context) -> anyhow::Result<T
     let server = server
         .listen(listener)
         .map_err(|e| anyhow::anyhow!(e))?
+        .shutdown_timeout(SHUTDOWN_TIMEOUT_SECS)
         .run();
+    let handle = server.handle();
+    let mut server = std::pin::pin!(server);
 
-    tokio::select! {
-        res = server => res.map_err(|e| anyhow::anyhow!(e)).map(|()| TaskOutcome::Failure),
-        outcome = shutdown_rx => outcome.map_err(|e| anyhow::anyhow!(e)),
-    }
+    let outcome = tokio::select! {
+        res = &mut server => {
+            return res.map_err(|e| anyhow::anyhow!(e)).map(|()| TaskOutcome::Failure);
+        }
+        outcome = shutdown_rx => outcome.map_err(|e| anyhow::anyhow!(e))?,
+    };
+
+    // End the event streams once observers have received the final events, and let the
+    // pending responses complete before stopping.
+    events.close();
+    drop(handle.stop(true));
+    server.await.map_err(|e| anyhow::anyhow!(e))?;
+    Ok(outcome)
 }
 
 pub async fn wait_until_ready(base_url: &str) -> Result<(), reqwest::Error> {
@@ -0,0 +1,107 @@
+//! Application of the configured [`RequestPolicy`] to chat completion requests.
+
+use serde_json::{json, Value};
+
+use crate::config::RequestPolicy;
+
+/// Rewrite the request according to the policy. Returns whether the request was changed.
+/// Denied models are checked separately with [`RequestPolicy::denies`]. A token limit is
+/// added as `max_tokens_param`, the parameter the provider expects.
+pub fn apply(policy: &RequestPolicy, request: &mut Value, max_tokens_param: &str) -> bool {
+    let Some(request) = request.as_object_mut() else {
+        return false;
+    };
+    let mut changed = false;
+
+    for param in &policy.strip_params {
+        changed |= request.remove(param).is_some();
+    }
+
+    if let Some(max_tokens) = policy.max_tokens {
+        // Limit the parameter the provider honours rather than one it would reject.
+        if max_tokens_param != "max_tokens" && !request.contains_key(max_tokens_param) {
+            if let Some(value) = request.remove("max_tokens") {
+                request.insert(max_tokens_param.to_owned(), value);
+                changed = true;
+            }
+        }
+        let mut limited = false;
+        for param in ["max_tokens", "max_completion_tokens"] {
+            let Some(value) = request.get_mut(param) else {
+                continue;
+            };
+            limited = true;
+            if value.as_u64().is_none_or(|tokens| tokens > max_tokens) {
+                *value = json!(max_tokens);
+                changed = true;
+            }
+        }
+        if !limited {
+            request.insert(max_tokens_param.to_owned(), json!(max_tokens));
+            changed = true;
+        }
+    }
+
+    let prepend = policy.system_prepend.as_deref();
+    let append = policy.system_append.as_deref();
+    if prepend.is_some() || append.is_some() {
+        if let Some(messages) = request.get_mut("messages").and_then(Value::as_array_mut) {
+            let system = messages
+                .iter_mut()
+                .find(|message| message["role"] == "system" || message["role"] == "developer");
+            match system {
+                Some(message) => surround(&mut message["content"], prepend, append),
+                None => messages.insert(
+                    0,
+                    json!({ "role": "system", "content": join(&[prepend, append]) }),
+                ),
+            }
+            changed = true;
+        }
+    }
+
+    changed
+}
+
+/// An OpenAI-style error body for a request to a denied model.
+pub fn denied_body(upstream_model: &str) -> Value {
+    json!({
+        "error": {
+            "message": format!(
+                "The model {upstream_model} is not allowed by the configured policy"
+            ),
+            "type": "invalid_request_error",
+            "param": "model",
+            "code": "model_not_allowed",
+        }
+    })
+}
+
+/// Add instructions before and after the content of a message.
+fn surround(content: &mut Value, prepend: Option<&str>, append: Option<&str>) {
+    match content {
+        // Multi-part content: add text parts.
+        Value::Array(parts) => {
+            if let Some(text) = prepend {
+                parts.insert(0, json!({ "type": "text", "text": text }));
+            }
+            if let Some(text) = append {
+                parts.push(json!({ "type": "text", "text": text }));
+            }
+        }
+        _ => {
+            let text = join(&[prepend, content.as_str(), append]);
+            *content = json!(text);
+        }
+    }
+}
+
+/// Join texts, separated by a blank line.
+fn join(texts: &[Option<&str>]) -> String {
+    texts
+        .iter()
+        .flatten()
+        .copied()
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
@@ -1,4 +1,6 @@
-use actix_web::{get, HttpResponse, Responder};
+use actix_web::{get, web, HttpResponse, Responder};
+
+use crate::context::Context;
 
 #[get("/ready")]
 async fn readiness() -> impl Responder {
@@ -9,3 +11,16 @@ async fn readiness() -> impl Responder {
 async fn healthz() -> impl Responder {
     HttpResponse::Ok().body("Healthy")
 }
+
+/// Serve the Prometheus metrics of the run to observers.
+#[get("")]
+async fn metrics(ctx: web::Data<Context>) -> impl Responder {
+    match ctx.metrics.render() {
+        Ok(text) => HttpResponse::Ok()
+            .content_type("text/plain; version=0.0.4")
+            .body(text),
+        Err(err) => {
+            HttpResponse::InternalServerError().body(format!("Failed to render metrics: {err}"))
+        }
+    }
+}
@@ -0,0 +1,250 @@
+//! Reassembly of streamed chat completions.
+
+use serde_json::{json, Map, Value};
+
+/// Reassembles a chat completion from the server-sent events of a streamed response.
+#[derive(Default)]
+pub struct StreamAssembler {
+    /// Received bytes that don't form a complete line yet. Chunks may end anywhere, even
+    /// within a UTF-8 character, so only complete lines are decoded.
+    buffer: Vec<u8>,
+    /// The data lines of the current event.
+    data: Vec<String>,
+    id: Option<Value>,
+    model: Option<Value>,
+    created: Option<Value>,
+    choices: Vec<Map<String, Value>>,
+    usage: Option<Value>,
+}
+
+impl StreamAssembler {
+    /// Feed a chunk of the response body.
+    pub fn push(&mut self, chunk: &[u8]) {
+        self.buffer.extend_from_slice(chunk);
+        let Some(end) = self.buffer.iter().rposition(|&byte| byte == b'\n') else {
+            return;
+        };
+        let rest = self.buffer.split_off(end + 1);
+        let complete = std::mem::replace(&mut self.buffer, rest);
+        for line in String::from_utf8_lossy(&complete).lines() {
+            self.handle_line(line);
+        }
+    }
+
+    /// Handle a line of the event stream. Events end with an empty line.
+    fn handle_line(&mut self, line: &str) {
+        if line.is_empty() {
+            let data = std::mem::take(&mut self.data).join("\n");
+            self.handle_event(&data);
+        } else if let Some(data) = line.strip_prefix("data:") {
+            self.data.push(data.trim_start().to_owned());
+        }
+    }
+
+    fn handle_event(&mut self, data: &str) {
+        if data.is_empty() || data == "[DONE]" {
+            return;
+        }
+        let Ok(chunk) = serde_json::from_str::<Value>(data) else {
+            log::debug!("Ignoring unparsable stream event: {data}");
+            return;
+        };
+
+        self.id = self.id.take().or_else(|| chunk.get("id").cloned());
+        self.model = self.model.take().or_else(|| chunk.get("model").cloned());
+        self.created = self
+            .created
+            .take()
+            .or_else(|| chunk.get("created").cloned());
+        if chunk["usage"].is_object() {
+            self.usage = Some(chunk["usage"].clone());
+        }
+
+        for choice in chunk["choices"].as_array().into_iter().flatten() {
+            let index = choice["index"].as_u64().unwrap_or(0) as usize;
+            if self.choices.len() <= index {
+                self.choices.resize_with(index + 1, Map::new);
+            }
+            merge_choice(&mut self.choices[index], choice);
+        }
+    }
+
+    /// The reassembled response in the format of a non-streamed chat completion. The
+    /// response has no `usage` if the upstream didn't report it.
+    pub fn finish(mut self) -> Value {
+        // The stream may end without a line break or an empty line after the last event.
+        let rest = std::mem::take(&mut self.buffer);
+        for line in String::from_utf8_lossy(&rest).lines() {
+            self.handle_line(line);
+        }
+        self.handle_line("");
+
+        let choices: Vec<Value> = self
+            .choices
+            .into_iter()
+            .enumerate()
+            .map(|(index, mut choice)| {
+                choice.insert("index".to_owned(), json!(index));
+                Value::Object(choice)
+            })
+            .collect();
+        let mut response = json!({
+            "object": "chat.completion",
+            "choices": choices,
+        });
+        for (key, value) in [
+            ("id", self.id),
+            ("model", self.model),
+            ("created", self.created),
+            ("usage", self.usage),
+        ] {
+            if let Some(value) = value {
+                response[key] = value;
+            }
+        }
+        response
+    }
+}
+
+/// Merge the delta of a streamed choice into the reassembled choice.
+fn merge_choice(choice: &mut Map<String, Value>, chunk: &Value) {
+    if let Some(finish_reason) = chunk["finish_reason"].as_str() {
+        choice.insert("finish_reason".to_owned(), json!(finish_reason));
+    }
+    let message = choice
+        .entry("message")
+        .or_insert_with(|| json!({ "role": "assistant" }));
+    let delta = &chunk["delta"];
+    if let Some(role) = delta["role"].as_str() {
+        message["role"] = json!(role);
+    }
+    if let Some(content) = delta["content"].as_str() {
+        append(message, "content", content);
+    }
+
+    for tool_call in delta["tool_calls"].as_array().into_iter().flatten() {
+        let index = tool_call["index"].as_u64().unwrap_or(0) as usize;
+        let tool_calls = message
+            .as_object_mut()
+            .expect("Message is an object")
+            .entry("tool_calls")
+            .or_insert_with(|| json!([]))
+            .as_array_mut()
+            .expect("Tool calls are an array");
+        if tool_calls.len() <= index {
+            tool_calls.resize_with(
+                index + 1,
+                || json!({ "type": "function", "function": { "name": "", "arguments": "" } }),
+            );
+        }
+        let call = &mut tool_calls[index];
+        if let Some(id) = tool_call["id"].as_str() {
+            call["id"] = json!(id);
+        }
+        if let Some(name) = tool_call["function"]["name"].as_str() {
+            append(&mut call["function"], "name", name);
+        }
+        if let Some(arguments) = tool_call["function"]["arguments"].as_str() {
+            append(&mut call["function"], "arguments", arguments);
+        }
+    }
+}
+
+fn append(object: &mut Value, key: &str, text: &str) {
+    let current = object[key].as_str().unwrap_or_default();
+    object[key] = json!(format!("{current}{text}"));
+}
+
+/// Tokens per message for the role and the separators between messages.
+const MESSAGE_OVERHEAD: u64 = 4;
+
+/// Rough token count of a text, for providers that don't report usage.
+/// Assumes about four characters per token, which is typical for English text and code.
+pub fn estimate_tokens(text: &str) -> u64 {
+    (text.chars().count() as u64).div_ceil(4)
+}
+
+/// Estimate the usage of a request and its reassembled response.
+pub fn estimate_usage(request: &Value, response: &Value) -> Value {
+    let prompt_tokens = estimate_prompt_tokens(request);
+    let completion_tokens: u64 = response["choices"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|choice| {
+            let message = &choice["message"];
+            let content = estimate_tokens(message["content"].as_str().unwrap_or_default());
+            let tool_calls: u64 = message["tool_calls"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|call| {
+                    estimate_tokens(call["function"]["arguments"].as_str().unwrap_or_default())
+                })
+                .sum();
+            content + tool_calls
+        })
+        .sum();
+    json!({
+        "prompt_tokens": prompt_tokens,
+        "completion_tokens": completion_tokens,
+        "total_tokens": prompt_tokens + completion_tokens,
+        "estimated": true,
+    })
+}
+
+/// Estimate the prompt tokens of a chat completion request, including tool calls and tool
+/// definitions.
+pub fn estimate_prompt_tokens(request: &Value) -> u64 {
+    let messages: u64 = request["messages"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(estimate_message_tokens)
+        .sum();
+    let tools = match &request["tools"] {
+        Value::Null => 0,
+        tools => estimate_tokens(&tools.to_string()),
+    };
+    messages + tools
+}
+
+/// Estimate the tokens of a message of a chat completion request.
+pub fn estimate_message_tokens(message: &Value) -> u64 {
+    let content = match &message["content"] {
+        Value::String(text) => estimate_tokens(text),
+        // Multi-part content: count the text parts.
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|part| part["text"].as_str())
+            .map(estimate_tokens)
+            .sum(),
+        _ => 0,
+    };
+    let tool_calls = match &message["tool_calls"] {
+        Value::Null => 0,
+        tool_calls => estimate_tokens(&tool_calls.to_string()),
+    };
+    MESSAGE_OVERHEAD + content + tool_calls
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reassembles_events_split_within_characters() {
+        let stream = "data: {\"choices\":[{\"index\":0,\"delta\":{\"content\":\"héllo\"}}]}\r\n\r\n\
+                      data: {\"choices\":[{\"index\":0,\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n\
+                      data: [DONE]";
+        let split = stream.find('é').unwrap() + 1;
+        let mut assembler = StreamAssembler::default();
+        assembler.push(&stream.as_bytes()[..split]);
+        assembler.push(&stream.as_bytes()[split..]);
+
+        let response = assembler.finish();
+        assert_eq!(response["choices"][0]["message"]["content"], "héllo");
+        assert_eq!(response["choices"][0]["finish_reason"], "stop");
+        assert!(response.get("usage").is_none());
+    }
+}
@@ -0,0 +1,461 @@
+//! Normalization of tool calling across providers.
+//!
+//! Requests are rewritten into the subset of OpenAI-style tool calling the provider supports,
+//! and responses, streamed or not, are rewritten into the strict OpenAI shape, so that agents
+//! can rely on it regardless of the provider.
+
+use std::collections::HashSet;
+
+use serde_json::{json, Value};
+
+use crate::providers::Provider;
+
+/// A tool calling feature requested by the agent that the provider doesn't support.
+pub struct Unsupported(pub String);
+
+/// Rewrite the tool calling parts of a request for the provider.
+/// Returns whether the request was changed.
+pub fn normalize_request(
+    request: &mut Value,
+    provider: &dyn Provider,
+) -> Result<bool, Unsupported> {
+    let capabilities = provider.tool_capabilities();
+    let name = provider.display_name();
+    let Some(request) = request.as_object_mut() else {
+        return Ok(false);
+    };
+    let mut changed = false;
+
+    // Translate the deprecated `functions` and `function_call` parameters.
+    if let Some(functions) = request.remove("functions") {
+        let tools: Vec<Value> = functions
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|function| json!({ "type": "function", "function": function }))
+            .collect();
+        request.insert("tools".to_owned(), Value::Array(tools));
+        changed = true;
+    }
+    if let Some(function_call) = request.remove("function_call") {
+        let tool_choice = match function_call {
+            Value::Object(call) => json!({ "type": "function", "function": call }),
+            other => other,
+        };
+        request.insert("tool_choice".to_owned(), tool_choice);
+        changed = true;
+    }
+
+    // Some agents send the arguments of earlier tool calls as JSON objects, like some
+    // providers return them, but strict providers only accept JSON strings.
+    let messages = request
+        .get_mut("messages")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten();
+    for message in messages {
+        let tool_calls = message
+            .get_mut("tool_calls")
+            .and_then(Value::as_array_mut)
+            .into_iter()
+            .flatten();
+        for call in tool_calls {
+            if let Some(arguments) = call.pointer_mut("/function/arguments") {
+                changed |= stringify_arguments(arguments);
+            }
+        }
+    }
+
+    let has_tools = request
+        .get("tools")
+        .and_then(Value::as_array)
+        .is_some_and(|tools| !tools.is_empty());
+    if has_tools && !capabilities.tools {
+        return Err(Unsupported(format!("{name} does not support tool calling")));
+    }
+
+    match request.get("tool_choice") {
+        Some(Value::String(choice))
+            if choice == "required" && !capabilities.tool_choice_required =>
+        {
+            return Err(Unsupported(format!(
+                "{name} does not support `tool_choice: \"required\"`"
+            )));
+        }
+        Some(Value::Object(_)) if !capabilities.tool_choice_function => {
+            return Err(Unsupported(format!(
+                "{name} does not support forcing a specific tool with `tool_choice`; \
+                 use \"auto\" or \"required\" instead"
+            )));
+        }
+        _ => {}
+    }
+
+    if !capabilities.parallel_tool_calls && request.remove("parallel_tool_calls").is_some() {
+        changed = true;
+    }
+
+    Ok(changed)
+}
+
+/// Rewrite the tool calls of a non-streamed response into the strict OpenAI shape:
+/// arguments as JSON strings, an ID and type for every call, `null` content alongside tool
+/// calls and a `tool_calls` finish reason. Returns whether the response was changed.
+pub fn normalize_response(response: &mut Value) -> bool {
+    let mut changed = false;
+    let choices = response
+        .get_mut("choices")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten();
+    for (choice_index, choice) in choices.enumerate() {
+        let Some(choice) = choice.as_object_mut() else {
+            continue;
+        };
+        let Some(message) = choice.get_mut("message").and_then(Value::as_object_mut) else {
+            continue;
+        };
+        let Some(tool_calls) = message
+            .get_mut("tool_calls")
+            .and_then(Value::as_array_mut)
+            .filter(|tool_calls| !tool_calls.is_empty())
+        else {
+            continue;
+        };
+
+        for (call_index, call) in tool_calls.iter_mut().enumerate() {
+            if !call.is_object() {
+                continue;
+            }
+            changed |= stringify_arguments(&mut call["function"]["arguments"]);
+            if !call["id"].is_string() {
+                call["id"] = json!(format!("call_{choice_index}_{call_index}"));
+                changed = true;
+            }
+            if call["type"] != "function" {
+                call["type"] = json!("function");
+                changed = true;
+            }
+        }
+
+        if message.get("content").is_some_and(|content| content == "") {
+            message.insert("content".to_owned(), Value::Null);
+            changed = true;
+        }
+        if choice
+            .get("finish_reason")
+            .is_some_and(|reason| reason == "stop")
+        {
+            choice.insert("finish_reason".to_owned(), json!("tool_calls"));
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Rewrites the tool calls in the events of a streamed response into the strict OpenAI shape,
+/// like [`normalize_response`] does for non-streamed responses: arguments as JSON strings, an
+/// index, ID and type for every call and a `tool_calls` finish reason.
+#[derive(Default)]
+pub struct StreamNormalizer {
+    /// Received bytes that don't form a complete line yet.
+    buffer: Vec<u8>,
+    /// The tool calls seen so far, by choice index and call index.
+    tool_calls: HashSet<(u64, u64)>,
+}
+
+impl StreamNormalizer {
+    /// Feed a chunk of the response body. Returns the normalized lines completed by it.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<u8> {
+        self.buffer.extend_from_slice(chunk);
+        let Some(end) = self.buffer.iter().rposition(|&byte| byte == b'\n') else {
+            return Vec::new();
+        };
+        let rest = self.buffer.split_off(end + 1);
+        let complete = std::mem::replace(&mut self.buffer, rest);
+        self.normalize_lines(&complete)
+    }
+
+    /// The normalized rest of the stream, once it ended.
+    pub fn finish(&mut self) -> Vec<u8> {
+        let rest = std::mem::take(&mut self.buffer);
+        self.normalize_lines(&rest)
+    }
+
+    fn normalize_lines(&mut self, lines: &[u8]) -> Vec<u8> {
+        let mut output = Vec::with_capacity(lines.len());
+        for line in lines.split_inclusive(|&byte| byte == b'\n') {
+            match self.normalize_line(line) {
+                Some(normalized) => output.extend(normalized),
+                None => output.extend_from_slice(line),
+            }
+        }
+        output
+    }
+
+    /// Normalize a `data` line of the event stream, keeping its line ending. Returns `None`
+    /// if the line doesn't need to change.
+    fn normalize_line(&mut self, line: &[u8]) -> Option<Vec<u8>> {
+        let data = line.strip_prefix(b"data:")?;
+        let mut chunk = serde_json::from_slice::<Value>(data).ok()?;
+        if !self.normalize_chunk(&mut chunk) {
+            return None;
+        }
+        let content_len = line.len()
+            - line
+                .iter()
+                .rev()
+                .take_while(|&&byte| byte == b'\r' || byte == b'\n')
+                .count();
+        let mut normalized = b"data: ".to_vec();
+        normalized.extend(serde_json::to_vec(&chunk).expect("JSON values serialize"));
+        normalized.extend_from_slice(&line[content_len..]);
+        Some(normalized)
+    }
+
+    /// Normalize the tool call deltas of a streamed chunk. Returns whether it was changed.
+    fn normalize_chunk(&mut self, chunk: &mut Value) -> bool {
+        let mut changed = false;
+        let choices = chunk
+            .get_mut("choices")
+            .and_then(Value::as_array_mut)
+            .into_iter()
+            .flatten();
+        for (position, choice) in choices.enumerate() {
+            let choice_index = choice["index"].as_u64().unwrap_or(position as u64);
+            let tool_calls = choice
+                .pointer_mut("/delta/tool_calls")
+                .and_then(Value::as_array_mut)
+                .into_iter()
+                .flatten();
+            for (position, call) in tool_calls.enumerate() {
+                let Some(call) = call.as_object_mut() else {
+                    continue;
+                };
+                let call_index = match call.get("index").and_then(Value::as_u64) {
+                    Some(index) => index,
+                    None => {
+                        call.insert("index".to_owned(), json!(position));
+                        changed = true;
+                        position as u64
+                    }
+                };
+                if let Some(arguments) = call
+                    .get_mut("function")
+                    .and_then(|function| function.get_mut("arguments"))
+                {
+                    // Deltas carry fragments of the arguments that are concatenated by the
+                    // agent, so a missing fragment is empty rather than an empty object.
+                    if arguments.is_null() {
+                        *arguments = json!("");
+                        changed = true;
+                    } else {
+                        changed |= stringify_arguments(arguments);
+                    }
+                }
+                // The first delta of a call carries its ID and type.
+                if self.tool_calls.insert((choice_index, call_index)) {
+                    if !call.get("id").is_some_and(Value::is_string) {
+                        call.insert(
+                            "id".to_owned(),
+                            json!(format!("call_{choice_index}_{call_index}")),
+                        );
+                        changed = true;
+                    }
+                    if call.get("type").is_none_or(|kind| kind != "function") {
+                        call.insert("type".to_owned(), json!("function"));
+                        changed = true;
+                    }
+                }
+            }
+
+            let has_tool_calls = self
+                .tool_calls
+                .iter()
+                .any(|&(index, _)| index == choice_index);
+            if has_tool_calls && choice["finish_reason"] == "stop" {
+                choice["finish_reason"] = json!("tool_calls");
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+/// Turn tool call arguments into a JSON string, if they aren't one already.
+/// Returns whether the arguments were changed.
+fn stringify_arguments(arguments: &mut Value) -> bool {
+    if arguments.is_string() {
+        return false;
+    }
+    let text = if arguments.is_null() {
+        "{}".to_owned()
+    } else {
+        arguments.to_string()
+    };
+    *arguments = Value::String(text);
+    true
+}
+
+/// An OpenAI-style error body for an unsupported tool calling feature.
+pub fn error_body(unsupported: &Unsupported) -> Value {
+    json!({
+        "error": {
+            "message": unsupported.0,
+            "type": "invalid_request_error",
+            "code": "unsupported_tool_feature",
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers;
+
+    #[test]
+    fn translates_functions_and_drops_unsupported_parameters() {
+        let mut request = json!({
+            "functions": [{ "name": "ls", "parameters": {} }],
+            "function_call": { "name": "ls" },
+            "parallel_tool_calls": true,
+        });
+        let changed = normalize_request(&mut request, providers::get("deepseek").unwrap());
+
+        assert!(changed.is_ok_and(|changed| changed));
+        assert_eq!(
+            request,
+            json!({
+                "tools": [{ "type": "function", "function": { "name": "ls", "parameters": {} } }],
+                "tool_choice": { "type": "function", "function": { "name": "ls" } },
+            })
+        );
+    }
+
+    #[test]
+    fn stringifies_tool_call_arguments_in_history() {
+        let mut request = json!({
+            "messages": [{
+                "role": "assistant",
+                "tool_calls": [
+                    { "id": "a", "function": { "name": "ls", "arguments": { "path": "." } } },
+                    { "id": "b", "function": { "name": "pwd", "arguments": "{}" } },
+                ],
+            }],
+        });
+        let changed = normalize_request(&mut request, providers::get("openai").unwrap());
+
+        assert!(changed.is_ok_and(|changed| changed));
+        let tool_calls = &request["messages"][0]["tool_calls"];
+        assert_eq!(tool_calls[0]["function"]["arguments"], r#"{"path":"."}"#);
+        assert_eq!(tool_calls[1]["function"]["arguments"], "{}");
+    }
+
+    #[test]
+    fn rejects_unsupported_tool_choice() {
+        let mut request = json!({
+            "tools": [{ "type": "function", "function": { "name": "ls" } }],
+            "tool_choice": { "type": "function", "function": { "name": "ls" } },
+        });
+        let result = normalize_request(&mut request, providers::get("cohere").unwrap());
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn normalizes_response_tool_calls() {
+        let mut response = json!({
+            "choices": [{
+                "message": {
+                    "content": "",
+                    "tool_calls": [{ "function": { "name": "ls", "arguments": { "path": "." } } }],
+                },
+                "finish_reason": "stop",
+            }],
+        });
+
+        assert!(normalize_response(&mut response));
+        assert_eq!(
+            response["choices"][0],
+            json!({
+                "message": {
+                    "content": null,
+                    "tool_calls": [{
+                        "id": "call_0_0",
+                        "type": "function",
+                        "function": { "name": "ls", "arguments": r#"{"path":"."}"# },
+                    }],
+                },
+                "finish_reason": "tool_calls",
+            })
+        );
+        assert!(!normalize_response(&mut response));
+    }
+
+    #[test]
+    fn normalizes_streamed_tool_calls() {
+        let mut normalizer = StreamNormalizer::default();
+        let first = normalizer.push(
+            b"data: {\"choices\":[{\"index\":0,\"delta\":{\"tool_calls\":[{\"function\":\
+              {\"name\":\"ls\",\"arguments\":{\"path\":\".\"}}}]}}]}\r\n\r\ndata: {\"choi",
+        );
+        let second =
+            normalizer.push(b"ces\":[{\"index\":0,\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n");
+        let rest = normalizer.finish();
+
+        let first = String::from_utf8(first).unwrap();
+        let (line, rest_of_first) = first.split_once("\r\n").unwrap();
+        assert_eq!(rest_of_first, "\r\n");
+        let chunk: Value = serde_json::from_str(line.strip_prefix("data: ").unwrap()).unwrap();
+        assert_eq!(
+            chunk["choices"][0]["delta"]["tool_calls"][0],
+            json!({
+                "index": 0,
+                "id": "call_0_0",
+                "type": "function",
+                "function": { "name": "ls", "arguments": r#"{"path":"."}"# },
+            })
+        );
+        let second = String::from_utf8(second).unwrap();
+        assert!(second.contains(r#""finish_reason":"tool_calls""#));
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn keeps_streamed_argument_fragments_concatenable() {
+        let mut normalizer = StreamNormalizer::default();
+        let deltas = [
+            r#"{"id":"call_1","type":"function","function":{"name":"ls","arguments":null}}"#,
+            r#"{"function":{"arguments":"{\"pa"}}"#,
+            r#"{"function":{"arguments":null}}"#,
+            r#"{"function":{"arguments":"th\":\".\"}"}}"#,
+        ];
+        let mut stream = String::new();
+        for delta in deltas {
+            let chunk = format!(
+                "data: {{\"choices\":[{{\"index\":0,\"delta\":{{\"tool_calls\":[{delta}]}}}}]}}\n\n"
+            );
+            stream.push_str(&String::from_utf8(normalizer.push(chunk.as_bytes())).unwrap());
+        }
+
+        let arguments: String = stream
+            .lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .map(|data| {
+                let chunk: Value = serde_json::from_str(data).unwrap();
+                let call = &chunk["choices"][0]["delta"]["tool_calls"][0];
+                call["function"]["arguments"].as_str().unwrap().to_owned()
+            })
+            .collect();
+        assert_eq!(arguments, r#"{"path":"."}"#);
+    }
+
+    #[test]
+    fn passes_streams_without_tool_calls_through() {
+        let stream = b"data: {\"choices\":[{\"index\":0,\"delta\":{\"content\":\"hi\"},\
+                       \"finish_reason\":\"stop\"}]}\n\ndata: [DONE]\n\n";
+        let mut normalizer = StreamNormalizer::default();
+
+        assert_eq!(normalizer.push(stream), stream);
+    }
+}
@@ -0,0 +1,101 @@
+//! Files uploaded by the agent that are kept with the run instead of being committed.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Maximum size of a single artifact.
+pub const MAX_ARTIFACT_SIZE: usize = 10 * 1024 * 1024;
+/// Maximum combined size of all artifacts of a run.
+pub const MAX_TOTAL_ARTIFACTS_SIZE: u64 = 100 * 1024 * 1024;
+
+#[derive(Debug)]
+pub enum ArtifactError {
+    InvalidName,
+    AlreadyExists,
+    QuotaExceeded,
+    Io(std::io::Error),
+}
+
+impl fmt::Display for ArtifactError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArtifactError::InvalidName => write!(f, "Invalid artifact file name"),
+            ArtifactError::AlreadyExists => write!(f, "An artifact with this name already exists"),
+            ArtifactError::QuotaExceeded => write!(f, "Artifact size quota exceeded"),
+            ArtifactError::Io(err) => write!(f, "Failed to store artifact: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ArtifactError {}
+
+/// Stores artifacts in a directory, enforcing the size quota.
+pub struct ArtifactStore {
+    dir: PathBuf,
+    /// Bytes used by the artifacts stored so far.
+    used: Mutex<u64>,
+}
+
+impl ArtifactStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            used: Mutex::new(0),
+        }
+    }
+
+    /// Directory in which the artifacts are stored.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Store an artifact under the given file name.
+    pub fn store(&self, name: &str, data: &[u8]) -> Result<PathBuf, ArtifactError> {
+        if !is_valid_name(name) {
+            return Err(ArtifactError::InvalidName);
+        }
+        if data.len() > MAX_ARTIFACT_SIZE {
+            return Err(ArtifactError::QuotaExceeded);
+        }
+
+        let mut used = self.used.lock().expect("Artifact lock poisoned");
+        if *used + data.len() as u64 > MAX_TOTAL_ARTIFACTS_SIZE {
+            return Err(ArtifactError::QuotaExceeded);
+        }
+
+        let path = self.dir.join(name);
+        if path.exists() {
+            return Err(ArtifactError::AlreadyExists);
+        }
+        fs::create_dir_all(&self.dir).map_err(ArtifactError::Io)?;
+        fs::write(&path, data).map_err(ArtifactError::Io)?;
+        *used += data.len() as u64;
+
+        Ok(path)
+    }
+
+    /// List the stored artifacts with their sizes, sorted by name.
+    pub fn list(&self) -> std::io::Result<Vec<(PathBuf, u64)>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut artifacts = fs::read_dir(&self.dir)?
+            .map(|entry| {
+                let entry = entry?;
+                Ok((entry.path(), entry.metadata()?.len()))
+            })
+            .collect::<std::io::Result<Vec<_>>>()?;
+        artifacts.sort();
+        Ok(artifacts)
+    }
+}
+
+/// Artifact names must be plain file names so they cannot escape the artifact directory.
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && Path::new(name).file_name().and_then(|n| n.to_str()) == Some(name)
+        && !name.contains(['/', '\\'])
+}
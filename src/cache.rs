@@ -0,0 +1,254 @@
+//! On-disk cache of chat completion responses.
+//!
+//! Identical requests to the same upstream model are answered from the cache, which saves
+//! time and tokens when re-running a task during agent development.
+
+use std::cmp::Reverse;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use anyhow::anyhow;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use url::Url;
+use uuid::Uuid;
+
+/// Time after which cached responses are discarded, if not configured.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Maximum total size of the cache, if not configured.
+pub const DEFAULT_MAX_SIZE: u64 = 512 * 1024 * 1024;
+
+pub struct ResponseCache {
+    dir: PathBuf,
+    ttl: Duration,
+    max_size: u64,
+    /// Total size of the cached responses, `None` until the cache directory was scanned.
+    /// Kept up to date on writes, so that the directory is only scanned again when the cache
+    /// may have outgrown its size limit.
+    size: Mutex<Option<u64>>,
+}
+
+impl ResponseCache {
+    pub fn new(dir: PathBuf, ttl: Duration, max_size: u64) -> Self {
+        Self {
+            dir,
+            ttl,
+            max_size,
+            size: Mutex::new(None),
+        }
+    }
+
+    /// Directory in which responses are cached by default.
+    pub fn default_dir() -> anyhow::Result<PathBuf> {
+        Ok(dirs::cache_dir()
+            .ok_or(anyhow!("Failed to locate appropriate cache directory"))?
+            .join("minion")
+            .join("responses"))
+    }
+
+    /// Cache key of a request body forwarded to `url`, with the model replaced by the
+    /// provider's name for it as in [`Upstream::post`](crate::providers::Upstream::post).
+    /// Requests that differ only in field order or in the alias used for the model share a key.
+    pub fn key(request: &Value, url: &Url, model: &str) -> String {
+        let mut request = request.clone();
+        if let Some(request) = request.as_object_mut() {
+            request.insert("model".to_owned(), Value::String(model.to_owned()));
+            // Only used for abuse monitoring by providers.
+            request.remove("user");
+        }
+        let normalized =
+            serde_json::to_vec(&sorted(json!({ "url": url.as_str(), "request": request })))
+                .expect("JSON values serialize");
+        format!("{:x}", Sha256::digest(normalized))
+    }
+
+    /// The cached response for the key, if present and not expired. Expired responses are
+    /// removed.
+    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let path = self.path(key);
+        let metadata = fs::metadata(&path).ok()?;
+        let fresh = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age < self.ttl);
+        if fresh {
+            return fs::read(&path).ok();
+        }
+
+        remove(&path);
+        if let Some(size) = self.size.lock().unwrap().as_mut() {
+            *size = size.saturating_sub(metadata.len());
+        }
+        None
+    }
+
+    /// Store a response and evict the oldest responses if the cache grew too large.
+    pub fn put(&self, key: &str, response: &[u8]) -> anyhow::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        // Written to a temporary file first, so that concurrent readers never see a partial
+        // response.
+        let tmp = self.dir.join(format!("{key}.{}.tmp", Uuid::now_v7()));
+        fs::write(&tmp, response)?;
+        if let Err(err) = fs::rename(&tmp, self.path(key)) {
+            remove(&tmp);
+            return Err(err.into());
+        }
+
+        let mut size = self.size.lock().unwrap();
+        match size.as_mut() {
+            Some(size) if *size + response.len() as u64 <= self.max_size => {
+                *size += response.len() as u64;
+            }
+            _ => *size = Some(self.evict()?),
+        }
+        Ok(())
+    }
+
+    /// Remove expired responses, then the oldest ones until the cache fits its size limit.
+    /// Returns the size of the remaining responses.
+    fn evict(&self) -> anyhow::Result<u64> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            // Skip responses that are still being written.
+            if entry
+                .path()
+                .extension()
+                .is_some_and(|extension| extension == "tmp")
+            {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            entries.push((modified, metadata.len(), entry.path()));
+        }
+        // Newest first.
+        entries.sort_by_key(|entry| Reverse(entry.0));
+
+        let mut total = 0;
+        let mut kept = 0;
+        for (modified, size, path) in entries {
+            total += size;
+            let expired = modified.elapsed().is_ok_and(|age| age >= self.ttl);
+            if expired || total > self.max_size {
+                remove(&path);
+            } else {
+                kept += size;
+            }
+        }
+        Ok(kept)
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.json"))
+    }
+}
+
+fn remove(path: &Path) {
+    if let Err(err) = fs::remove_file(path) {
+        log::warn!(
+            "Failed to remove cached response {}: {err}",
+            path.to_string_lossy()
+        );
+    }
+}
+
+/// Rebuild a JSON value with the keys of all objects in sorted order.
+fn sorted(value: Value) -> Value {
+    match value {
+        Value::Object(object) => {
+            let mut entries: Vec<_> = object.into_iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, sorted(value)))
+                    .collect::<Map<_, _>>(),
+            )
+        }
+        Value::Array(values) => Value::Array(values.into_iter().map(sorted).collect()),
+        value => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    #[test]
+    fn key_ignores_field_order_model_alias_and_user() {
+        let request = json!({
+            "model": "fast",
+            "messages": [{ "role": "user", "content": "Hi" }],
+            "temperature": 0,
+        });
+        let reordered = json!({
+            "temperature": 0,
+            "user": "agent",
+            "messages": [{ "content": "Hi", "role": "user" }],
+            "model": "groq/llama-3.1-8b-instant",
+        });
+        let url = url("https://api.groq.com/openai/v1/chat/completions");
+
+        assert_eq!(
+            ResponseCache::key(&request, &url, "llama-3.1-8b-instant"),
+            ResponseCache::key(&reordered, &url, "llama-3.1-8b-instant")
+        );
+    }
+
+    #[test]
+    fn key_depends_on_request_endpoint_and_model() {
+        let request = json!({ "messages": [{ "role": "user", "content": "Hi" }] });
+        let other = json!({ "messages": [{ "role": "user", "content": "Hello" }] });
+        let openai = url("https://api.openai.com/v1/chat/completions");
+        let azure =
+            url("https://example.openai.azure.com/openai/deployments/gpt-4o/chat/completions");
+
+        assert_ne!(
+            ResponseCache::key(&request, &openai, "gpt-4o"),
+            ResponseCache::key(&request, &openai, "gpt-4o-mini")
+        );
+        assert_ne!(
+            ResponseCache::key(&request, &openai, "gpt-4o"),
+            ResponseCache::key(&other, &openai, "gpt-4o")
+        );
+        assert_ne!(
+            ResponseCache::key(&request, &openai, "gpt-4o"),
+            ResponseCache::key(&request, &azure, "gpt-4o")
+        );
+    }
+
+    #[test]
+    fn get_removes_expired_responses() {
+        let dir = tempfile::tempdir().unwrap();
+        ResponseCache::new(dir.path().to_owned(), DEFAULT_TTL, DEFAULT_MAX_SIZE)
+            .put("key", b"{}")
+            .unwrap();
+
+        let cache = ResponseCache::new(dir.path().to_owned(), Duration::ZERO, DEFAULT_MAX_SIZE);
+        assert!(cache.path("key").exists());
+        assert_eq!(cache.get("key"), None);
+        assert!(!cache.path("key").exists());
+    }
+
+    #[test]
+    fn put_replaces_responses() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ResponseCache::new(dir.path().to_owned(), DEFAULT_TTL, DEFAULT_MAX_SIZE);
+
+        cache.put("key", b"{}").unwrap();
+        cache.put("key", b"[]").unwrap();
+        assert_eq!(cache.get("key"), Some(b"[]".to_vec()));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
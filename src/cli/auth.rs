@@ -0,0 +1,117 @@
+use anyhow::anyhow;
+use clap::builder::PossibleValuesParser;
+use clap::Subcommand;
+use futures::future::join_all;
+
+use crate::config::Config;
+use crate::providers::{self, Provider};
+use crate::secrets;
+use crate::util::mask_secret;
+
+#[derive(Subcommand)]
+pub enum AuthCommand {
+    /// Show the configured LLM providers and check their API keys
+    Status,
+    /// Make an LLM provider the default
+    Default {
+        #[clap(value_parser = PossibleValuesParser::new(providers::ids()))]
+        llm_provider: String,
+    },
+}
+
+pub async fn exec(command: AuthCommand, mut config: Config) -> anyhow::Result<()> {
+    match command {
+        AuthCommand::Status => status(&config).await,
+        AuthCommand::Default { llm_provider } => {
+            let provider = providers::get(&llm_provider).expect("Provider validated by clap");
+            let profile = config.profile_mut();
+            if profile.key(provider).is_none() {
+                return Err(anyhow!(
+                    "{} is not configured. Run `minion login {}` first.",
+                    provider.display_name(),
+                    provider.id()
+                ));
+            }
+            profile.llm_provider = Some(provider.id().to_owned());
+            config.save()?;
+            println!(
+                "{} is now the default LLM provider of {}.",
+                provider.display_name(),
+                config.profile_description()
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Print each provider with its masked key and whether the key is accepted.
+async fn status(config: &Config) -> anyhow::Result<()> {
+    let profile = config.profile();
+    println!("Providers of {}:", config.profile_description());
+
+    let checks = providers::PROVIDERS
+        .iter()
+        .copied()
+        .map(|provider| async move {
+            let Some(key) = profile.key(provider) else {
+                return (provider, "not configured".to_owned());
+            };
+            let key = match secrets::resolve(key) {
+                Ok(key) => key,
+                Err(err) => return (provider, format!("failed to read key: {err}")),
+            };
+            let settings = profile
+                .providers
+                .get(provider.id())
+                .cloned()
+                .unwrap_or_default();
+            let result = match provider.verify_key(&key, &settings).await {
+                Ok(()) => "valid".to_owned(),
+                Err(err) => format!("invalid: {err}"),
+            };
+            (provider, format!("{}  {result}", mask_secret(&key)))
+        });
+
+    for (provider, status) in join_all(checks).await {
+        let default = if profile.llm_provider.as_deref() == Some(provider.id()) {
+            " (default)"
+        } else {
+            ""
+        };
+        println!(
+            "  {:<24}{status}",
+            format!("{}{default}", provider.display_name())
+        );
+    }
+    Ok(())
+}
+
+/// Remove the API key of a provider from the selected profile and from secret storage.
+pub fn logout(provider: &dyn Provider, mut config: Config) -> anyhow::Result<()> {
+    let profile = config.profile_mut();
+    let Some(key) = profile.keys.remove(provider.id()) else {
+        return Err(anyhow!("{} is not configured.", provider.display_name()));
+    };
+    secrets::delete(&key)?;
+
+    if profile.llm_provider.as_deref() == Some(provider.id()) {
+        // Fall back to another configured provider, if any.
+        let fallback = profile.first_configured_provider();
+        profile.llm_provider = fallback.map(|provider| provider.id().to_owned());
+        match fallback {
+            Some(provider) => println!(
+                "{} is now the default LLM provider.",
+                provider.display_name()
+            ),
+            None => println!("No LLM provider is configured anymore."),
+        }
+    }
+    config.save()?;
+
+    println!(
+        "Removed the {} API key from {}.",
+        provider.display_name(),
+        config.profile_description()
+    );
+    Ok(())
+}
@@ -0,0 +1,311 @@
+use std::fs;
+
+use anyhow::anyhow;
+use clap::Subcommand;
+
+use crate::config::Config;
+use crate::prompt;
+use crate::secrets::SecretRef;
+use crate::util::mask_secret;
+
+use super::editor;
+
+/// Settings that are never part of a profile.
+const GLOBAL_KEYS: &[&str] = &["secret_storage", "profiles"];
+
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Print the value of a setting
+    Get {
+        /// Setting, e.g. `llm_provider` or `run.image`
+        key: String,
+    },
+    /// Change the value of a setting
+    Set {
+        /// Setting, e.g. `llm_provider` or `run.image`
+        key: String,
+        /// New value, parsed as TOML if possible and as a string otherwise
+        value: String,
+    },
+    /// Remove a setting
+    Unset {
+        /// Setting, e.g. `llm_provider` or `run.image`
+        key: String,
+    },
+    /// List all settings
+    List,
+    /// Open the config file in an editor
+    Edit,
+    /// Print the path of the config file
+    Path,
+    /// Check the config file for errors
+    Validate,
+}
+
+pub fn exec(command: ConfigCommand, profile: Option<String>) -> anyhow::Result<()> {
+    let path = Config::filepath()?;
+
+    match command {
+        ConfigCommand::Path => println!("{}", path.to_string_lossy()),
+        ConfigCommand::Get { key } => {
+            let table = load_table()?;
+            let key = profile_key(&key, profile.as_deref());
+            let value =
+                lookup(&table, &key).ok_or_else(|| anyhow!("Setting `{key}` is not set"))?;
+            println!("{}", display_value(&key, value));
+        }
+        ConfigCommand::Set { key, value } => {
+            let table = load_table()?;
+            let key = profile_key(&key, profile.as_deref());
+            let value = parse_value(table, &key, &value)?;
+            write_setting(&key, Some(&value))?;
+        }
+        ConfigCommand::Unset { key } => {
+            let mut table = load_table()?;
+            let key = profile_key(&key, profile.as_deref());
+            if remove(&mut table, &key).is_none() {
+                return Err(anyhow!("Setting `{key}` is not set"));
+            }
+            Config::from_table(table)?;
+            write_setting(&key, None)?;
+        }
+        ConfigCommand::List => {
+            let table = load_table()?;
+            let mut entries = Vec::new();
+            flatten(&toml::Value::Table(table), "", &mut entries);
+            for (key, value) in entries {
+                println!("{key} = {}", display_value(&key, &value));
+            }
+        }
+        ConfigCommand::Edit => edit()?,
+        ConfigCommand::Validate => {
+            let text = fs::read_to_string(&path)
+                .map_err(|err| anyhow!("Failed to read {}: {err}", path.to_string_lossy()))?;
+            let (_, unknown_keys) = Config::parse(&text)
+                .map_err(|err| anyhow!("Invalid config file {}: {err}", path.to_string_lossy()))?;
+            if !unknown_keys.is_empty() {
+                return Err(anyhow!(
+                    "Unknown settings in {}: {}",
+                    path.to_string_lossy(),
+                    unknown_keys.join(", ")
+                ));
+            }
+            println!("The config file at {} is valid.", path.to_string_lossy());
+        }
+    }
+
+    Ok(())
+}
+
+/// Load the config file as a TOML table, without interpreting it.
+fn load_table() -> anyhow::Result<toml::Table> {
+    let path = Config::filepath()?;
+    if !path.exists() {
+        return Ok(toml::Table::new());
+    }
+    let text = fs::read_to_string(&path)?;
+    toml::from_str(&text).map_err(|err| {
+        anyhow!(
+            "Invalid config file at {}: {err}\nRun `minion config edit` to fix it.",
+            path.to_string_lossy()
+        )
+    })
+}
+
+/// Qualify a key with the selected profile.
+fn profile_key(key: &str, profile: Option<&str>) -> String {
+    let top_level = key.split('.').next().unwrap_or(key);
+    match profile {
+        Some(profile) if !GLOBAL_KEYS.contains(&top_level) => format!("profiles.{profile}.{key}"),
+        _ => key.to_owned(),
+    }
+}
+
+fn lookup<'a>(table: &'a toml::Table, key: &str) -> Option<&'a toml::Value> {
+    let mut parts = key.split('.');
+    let mut value = table.get(parts.next()?)?;
+    for part in parts {
+        value = value.as_table()?.get(part)?;
+    }
+    Some(value)
+}
+
+fn remove(table: &mut toml::Table, key: &str) -> Option<toml::Value> {
+    match key.split_once('.') {
+        Some((head, rest)) => remove(table.get_mut(head)?.as_table_mut()?, rest),
+        None => table.remove(key),
+    }
+}
+
+fn insert(table: &mut toml::Table, key: &str, value: toml::Value) -> anyhow::Result<()> {
+    match key.split_once('.') {
+        Some((head, rest)) => {
+            let entry = table
+                .entry(head)
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+            let table = entry
+                .as_table_mut()
+                .ok_or_else(|| anyhow!("Setting `{head}` is not a table"))?;
+            insert(table, rest, value)
+        }
+        None => {
+            table.insert(key.to_owned(), value);
+            Ok(())
+        }
+    }
+}
+
+/// Parse the value of a setting, trying the possible interpretations of the given text until
+/// one fits the config schema when set in the given table.
+fn parse_value(table: toml::Table, key: &str, value: &str) -> anyhow::Result<toml::Value> {
+    let mut candidates = Vec::new();
+    if let Ok(parsed) = toml::from_str::<toml::Table>(&format!("value = {value}")) {
+        let parsed = parsed["value"].clone();
+        if let toml::Value::Integer(i) = parsed {
+            candidates.push(toml::Value::Integer(i));
+            candidates.push(toml::Value::Float(i as f64));
+        } else {
+            candidates.push(parsed);
+        }
+    }
+    candidates.push(toml::Value::String(value.to_owned()));
+
+    let mut last_err = None;
+    for candidate in candidates {
+        let mut attempt = table.clone();
+        insert(&mut attempt, key, candidate.clone())?;
+        match Config::from_table(attempt) {
+            Ok((_, unknown_keys))
+                if unknown_keys
+                    .iter()
+                    .any(|k| key == k || key.starts_with(&format!("{k}."))) =>
+            {
+                return Err(anyhow!("Unknown setting `{key}`"));
+            }
+            Ok(_) => return Ok(candidate),
+            Err(err) => last_err = Some(err),
+        }
+    }
+    Err(anyhow!(
+        "Invalid value for `{key}`: {}",
+        last_err.expect("At least one candidate was tried")
+    ))
+}
+
+/// Set or remove a single setting in the config file, keeping the rest of the file, including
+/// comments and the order of settings, as it is.
+fn write_setting(key: &str, value: Option<&toml::Value>) -> anyhow::Result<()> {
+    let path = Config::filepath()?;
+    let text = fs::read_to_string(&path).unwrap_or_default();
+    let mut document = text.parse::<toml_edit::DocumentMut>()?;
+
+    let value = value
+        .map(|value| value.to_string().parse::<toml_edit::Value>())
+        .transpose()?;
+    edit_setting(document.as_table_mut(), key, value)?;
+
+    fs::create_dir_all(path.parent().unwrap())?;
+    fs::write(&path, document.to_string())?;
+    Ok(())
+}
+
+/// Set or remove a setting in a TOML document table. Tables emptied by removing the setting
+/// are removed too.
+fn edit_setting(
+    table: &mut dyn toml_edit::TableLike,
+    key: &str,
+    value: Option<toml_edit::Value>,
+) -> anyhow::Result<()> {
+    match key.split_once('.') {
+        Some((head, rest)) => {
+            if value.is_none() && table.get(head).is_none() {
+                return Ok(());
+            }
+            let inner = table
+                .entry(head)
+                .or_insert_with(|| {
+                    let mut table = toml_edit::Table::new();
+                    table.set_implicit(true);
+                    toml_edit::Item::Table(table)
+                })
+                .as_table_like_mut()
+                .ok_or_else(|| anyhow!("Setting `{head}` is not a table"))?;
+            edit_setting(inner, rest, value)?;
+            if inner.is_empty() {
+                table.remove(head);
+            }
+        }
+        None => match (value, table.get_mut(key)) {
+            // Replace the value in place to keep the comments around it.
+            (Some(mut value), Some(toml_edit::Item::Value(old))) => {
+                *value.decor_mut() = old.decor().clone();
+                *old = value;
+            }
+            (Some(value), _) => {
+                table.insert(key, toml_edit::Item::Value(value));
+            }
+            (None, _) => {
+                table.remove(key);
+            }
+        },
+    }
+    Ok(())
+}
+
+/// Flatten a TOML value into dotted keys and leaf values.
+fn flatten(value: &toml::Value, prefix: &str, entries: &mut Vec<(String, toml::Value)>) {
+    match value.as_table() {
+        Some(table) => {
+            for (key, value) in table {
+                flatten(value, &format!("{prefix}{key}."), entries);
+            }
+        }
+        None => entries.push((prefix.trim_end_matches('.').to_owned(), value.clone())),
+    }
+}
+
+/// Format a value for display, masking API keys that are stored in plaintext.
+fn display_value(key: &str, value: &toml::Value) -> String {
+    // API keys live in `keys` tables; earlier versions used `<provider>_key` fields.
+    let is_api_key = key.ends_with("_key") || key.rsplit('.').nth(1) == Some("keys");
+    match value.as_str() {
+        Some(secret) if is_api_key && SecretRef::parse(secret).is_none() => {
+            format!("\"{}\"", mask_secret(secret))
+        }
+        _ => value.to_string(),
+    }
+}
+
+/// Edit the config file in an editor, validating it before saving.
+fn edit() -> anyhow::Result<()> {
+    let path = Config::filepath()?;
+    let mut text = fs::read_to_string(&path).unwrap_or_default();
+
+    loop {
+        let Some(edited) = editor::Editor::new().extension(".toml").edit(&text)? else {
+            println!("No changes made.");
+            return Ok(());
+        };
+        text = edited;
+
+        let error = match Config::parse(&text) {
+            Ok((_, unknown_keys)) if unknown_keys.is_empty() => None,
+            Ok((_, unknown_keys)) => Some(format!("Unknown settings: {}", unknown_keys.join(", "))),
+            Err(err) => Some(err.to_string()),
+        };
+        let Some(error) = error else {
+            break;
+        };
+
+        eprintln!("{error}");
+        println!("Edit again?");
+        if !prompt::confirm(Some(true))? {
+            return Err(anyhow!("Config file not saved."));
+        }
+    }
+
+    fs::create_dir_all(path.parent().unwrap())?;
+    fs::write(&path, text)?;
+    println!("Saved {}", path.to_string_lossy());
+    Ok(())
+}
@@ -90,6 +90,12 @@ impl Editor {
         }
     }
 
+    /// Sets a specific extension for the file, e.g. for syntax highlighting.
+    pub fn extension(&mut self, val: &str) -> &mut Self {
+        self.extension = val.into();
+        self
+    }
+
     /// Launches the editor to edit a string.
     ///
     /// Returns `None` if the file was not saved or otherwise the
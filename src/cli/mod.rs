@@ -1,12 +1,19 @@
-use std::path::PathBuf;
+use std::io::Read as _;
+use std::path::{Path, PathBuf};
 
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::builder::PossibleValuesParser;
+use clap::{Parser, Subcommand};
 
-use crate::config::{Config, LLMProvider};
-use crate::providers::{cohere, gemini, groq, openrouter};
+use crate::config::{Config, LLMRouterTable, RunSettings, PROJECT_CONFIG_FILENAME};
+use crate::prompt;
+use crate::providers;
 
+mod auth;
+mod config;
 mod editor;
+mod models;
 mod run;
+mod usage;
 
 #[derive(Subcommand)]
 enum Command {
@@ -22,11 +29,56 @@ enum Command {
         /// Expose a Docker socket to the container
         #[clap(long)]
         nested: bool,
+        /// Answer identical LLM requests from an on-disk cache
+        #[clap(long)]
+        cache: bool,
     },
     /// Login using one of the supported LLM providers
     Login {
-        #[clap(value_enum)]
-        llm_provider: LLMProvider,
+        #[clap(value_parser = PossibleValuesParser::new(providers::ids()))]
+        llm_provider: String,
+        /// Read the API key from stdin instead of running the interactive login flow
+        #[clap(long, conflicts_with_all = ["api_key_env", "api_key_file"])]
+        api_key_stdin: bool,
+        /// Read the API key from the given environment variable whenever it is needed
+        #[clap(long, value_name = "VAR", conflicts_with = "api_key_file")]
+        api_key_env: Option<String>,
+        /// Read the API key from the given file
+        #[clap(long, value_name = "PATH")]
+        api_key_file: Option<PathBuf>,
+    },
+    /// Remove the API key of an LLM provider
+    Logout {
+        #[clap(value_parser = PossibleValuesParser::new(providers::ids()))]
+        llm_provider: String,
+    },
+    /// Manage LLM provider authentication
+    Auth {
+        #[clap(subcommand)]
+        command: auth::AuthCommand,
+    },
+    /// Read and modify the configuration
+    Config {
+        #[clap(subcommand)]
+        command: config::ConfigCommand,
+    },
+    /// List the models available from the configured LLM providers
+    Models {
+        /// Only list the models of this provider
+        #[clap(long, value_parser = PossibleValuesParser::new(providers::ids()))]
+        provider: Option<String>,
+        /// Fetch the model listings again instead of using cached ones
+        #[clap(long)]
+        refresh: bool,
+    },
+    /// Show the LLM usage and estimated cost of recent runs
+    Usage {
+        /// Include the runs of the last N days
+        #[clap(long, value_name = "N", conflicts_with = "weeks")]
+        days: Option<u64>,
+        /// Include the runs of the last N weeks
+        #[clap(long, value_name = "N")]
+        weeks: Option<u64>,
     },
 }
 
@@ -39,6 +91,9 @@ struct Cli {
     /// Enable debug logging
     #[clap(long)]
     debug: bool,
+    /// Use the named configuration profile
+    #[clap(long, global = true, env = "MINION_PROFILE")]
+    profile: Option<String>,
     #[clap(subcommand)]
     command: Option<Command>,
 }
@@ -65,27 +120,23 @@ pub fn exec() {
         message: None,
         containerfile: None,
         nested: false,
+        cache: false,
     }) {
         Command::Run {
             message,
             containerfile,
             nested,
+            cache,
         } => {
-            let config = Config::load_or_create().expect("Failed to load config");
-            let Some(llm_router_table) = config.llm_router_table() else {
-                eprintln!("You currently don't have a LLM API key configured.");
-                eprintln!("Run `minion login` to authenticate with a supported provider.");
-                eprintln!(
-                    "Supported providers: {}",
-                    LLMProvider::value_variants()
-                        .iter()
-                        .map(|p| p.to_string())
-                        .collect::<Vec<_>>()
-                        .join(", ")
-                );
-                eprintln!("If your LLM provider is not listed, please contribute!");
-                std::process::exit(1);
+            let repo_path = std::env::current_dir().expect("Failed to get current dir");
+            let options = RunSettings {
+                containerfile,
+                nested: nested.then_some(true),
+                cache: cache.then_some(true),
+                ..Default::default()
             };
+            let (llm_router_table, settings) =
+                load_run_config(cli.profile, &repo_path, Some(options));
 
             let task_description = if let Some(msg) = message {
                 msg
@@ -101,43 +152,226 @@ pub fn exec() {
             tokio::runtime::Runtime::new()
                 .expect("Failed to create runtime")
                 .block_on(async {
-                    run::run(
-                        llm_router_table,
-                        &containerfile,
-                        nested,
-                        &std::env::current_dir().expect("Failed to get current dir"),
-                        task_description,
-                    )
-                    .await
-                    .expect("Failed to run task");
+                    run::run(llm_router_table, &settings, &repo_path, task_description)
+                        .await
+                        .expect("Failed to run task");
                 });
         }
         Command::Login {
-            llm_provider: provider,
+            llm_provider,
+            api_key_stdin,
+            api_key_env,
+            api_key_file,
         } => {
+            let provider = providers::get(&llm_provider).expect("Provider validated by clap");
+            let mut config = load_config();
+            config.select_profile(cli.profile);
+
+            let key = if api_key_stdin {
+                let mut input = String::new();
+                std::io::stdin()
+                    .read_to_string(&mut input)
+                    .expect("Failed to read API key from stdin");
+                let key = input.trim();
+                if key.lines().filter(|line| !line.trim().is_empty()).count() > 1 {
+                    eprintln!("Expected a single API key on stdin, got several lines.");
+                    std::process::exit(1);
+                }
+                Some(key.to_owned())
+            } else if let Some(var) = api_key_env {
+                if std::env::var_os(&var).is_none() {
+                    eprintln!("Environment variable {var} is not set.");
+                    std::process::exit(1);
+                }
+                Some(format!("env:{var}"))
+            } else {
+                api_key_file.map(|path| {
+                    std::fs::read_to_string(&path)
+                        .unwrap_or_else(|err| {
+                            eprintln!("Failed to read {}: {err}", path.to_string_lossy());
+                            std::process::exit(1);
+                        })
+                        .trim()
+                        .to_owned()
+                })
+            };
+
+            let result = match key {
+                Some(key) if key.is_empty() => Err(anyhow::anyhow!("No API key provided.")),
+                Some(key) => providers::save_key(config, provider, key),
+                None => tokio::runtime::Runtime::new()
+                    .expect("Failed to create runtime")
+                    .block_on(providers::login_flow(provider, config)),
+            };
+            if let Err(err) = result {
+                eprintln!("Login failed: {err}");
+                std::process::exit(1);
+            }
+        }
+        Command::Logout { llm_provider } => {
+            let provider = providers::get(&llm_provider).expect("Provider validated by clap");
+            let mut config = load_config();
+            config.select_profile(cli.profile);
+            if let Err(err) = auth::logout(provider, config) {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        }
+        Command::Auth { command } => {
+            let mut config = load_config();
+            config.select_profile(cli.profile);
+            if !config.selected_profile_exists() {
+                eprintln!("The {} does not exist.", config.profile_description());
+                std::process::exit(1);
+            }
+            tokio::runtime::Runtime::new()
+                .expect("Failed to create runtime")
+                .block_on(async {
+                    if let Err(err) = auth::exec(command, config).await {
+                        eprintln!("{err}");
+                        std::process::exit(1);
+                    }
+                });
+        }
+        Command::Config { command } => {
+            if let Err(err) = config::exec(command, cli.profile) {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        }
+        Command::Models { provider, refresh } => {
+            let repo_path = std::env::current_dir().expect("Failed to get current dir");
+            let (llm_router_table, _) = load_run_config(cli.profile, &repo_path, None);
             tokio::runtime::Runtime::new()
                 .expect("Failed to create runtime")
                 .block_on(async {
-                    let config = Config::load_or_create().expect("Failed to load config");
-                    match provider {
-                        LLMProvider::OpenRouter => openrouter::login_flow(config)
-                            .await
-                            .expect("Failed to start login flow"),
-                        LLMProvider::Groq => groq::login_flow(config)
-                            .await
-                            .expect("Failed to start login flow"),
-                        LLMProvider::GoogleGemini => gemini::login_flow(config)
-                            .await
-                            .expect("Failed to start login flow"),
-                        LLMProvider::Cohere => cohere::login_flow(config)
-                            .await
-                            .expect("Failed to start login flow"),
+                    if let Err(err) = models::exec(&llm_router_table, provider, refresh).await {
+                        eprintln!("{err}");
+                        std::process::exit(1);
                     }
                 });
         }
+        Command::Usage { days, weeks } => {
+            let days = weeks
+                .map(|weeks| weeks.saturating_mul(7))
+                .or(days)
+                .unwrap_or(7);
+            if let Err(err) = usage::exec(days) {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        }
     }
 }
 
+/// Load the router table and run settings for the selected profile and the repository,
+/// exiting with an error message if no LLM provider is configured. `options` are the run
+/// settings given on the command line when running an agent, which take precedence.
+fn load_run_config(
+    profile: Option<String>,
+    repo_path: &Path,
+    options: Option<RunSettings>,
+) -> (LLMRouterTable, RunSettings) {
+    let mut config = Config::load_or_default().unwrap_or_else(|err| {
+        eprintln!("Failed to load config: {err}");
+        std::process::exit(1);
+    });
+    config.select_profile(profile);
+    if !config.selected_profile_exists() {
+        eprintln!("The {} does not exist.", config.profile_description());
+        std::process::exit(1);
+    }
+    if let Err(err) = config.apply_env_overrides() {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+    let project_settings = RunSettings::load_project(repo_path).unwrap_or_else(|err| {
+        eprintln!("{err}");
+        std::process::exit(1);
+    });
+    let user_settings = config.run_settings();
+    let mut project_settings = project_settings.unwrap_or_default();
+    if let Some(options) = &options {
+        vet_project_settings(&mut project_settings, &user_settings, options);
+    }
+    let settings = user_settings
+        .merge(project_settings)
+        .merge(options.unwrap_or_default());
+
+    let llm_router_table = config
+        .llm_router_table(settings.models.clone())
+        .unwrap_or_else(|err| {
+            eprintln!("Failed to load LLM API keys: {err}");
+            std::process::exit(1);
+        });
+    let Some(llm_router_table) = llm_router_table else {
+        eprintln!("You currently don't have a LLM API key configured.");
+        eprintln!("Run `minion login` to authenticate with a supported provider.");
+        eprintln!(
+            "Alternatively, provide a key in an environment variable such as {}.",
+            providers::key_env_var(&providers::groq::Groq)
+        );
+        eprintln!("Supported providers: {}", providers::ids().join(", "));
+        eprintln!("If your LLM provider is not listed, please contribute!");
+        std::process::exit(1);
+    };
+    (llm_router_table, settings)
+}
+
+/// Keep the repository's `.minion.toml` from deciding how the agent's container runs without
+/// the user's consent: it can't expose the Docker socket unless the user does, and the user
+/// confirms the image it chooses before it's built or run.
+fn vet_project_settings(project: &mut RunSettings, user: &RunSettings, options: &RunSettings) {
+    if project.nested == Some(true) && user.nested != Some(true) && options.nested.is_none() {
+        eprintln!(
+            "Ignoring `nested = true` in {PROJECT_CONFIG_FILENAME}. \
+             Pass --nested to expose the Docker socket to the agent."
+        );
+        project.nested = None;
+    }
+
+    // The containerfile takes precedence over the image.
+    let request = if options.containerfile.is_some() {
+        None
+    } else if let Some(containerfile) = &project.containerfile {
+        (user.containerfile.as_ref() != Some(containerfile)).then(|| {
+            format!(
+                "build the agent image from {}",
+                containerfile.to_string_lossy()
+            )
+        })
+    } else if let Some(image) = &project.image {
+        (user.containerfile.is_none() && user.image.as_ref() != Some(image))
+            .then(|| format!("run the agent in the image {image}"))
+    } else {
+        None
+    };
+    let Some(request) = request else {
+        return;
+    };
+    println!("The {PROJECT_CONFIG_FILENAME} file of this repository asks to {request}.");
+    println!("Continue?");
+    match prompt::confirm(Some(false)) {
+        Ok(true) => {}
+        Ok(false) => {
+            eprintln!("Aborted.");
+            std::process::exit(1);
+        }
+        Err(err) => {
+            eprintln!("Failed to read answer: {err}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Load the config, exiting with an error message if it is invalid.
+fn load_config() -> Config {
+    Config::load_or_create().unwrap_or_else(|err| {
+        eprintln!("Failed to load config: {err}");
+        std::process::exit(1);
+    })
+}
+
 fn read_task_from_editor() -> String {
     let initial_message =
         "\n\n# Please describe your task. Lines starting with '#' will be ignored.";
@@ -0,0 +1,95 @@
+use anyhow::anyhow;
+use futures::future::join_all;
+
+use crate::config::LLMRouterTable;
+use crate::models;
+use crate::providers::ModelInfo;
+
+/// Print the models of each configured provider with the aliases resolving to them.
+pub async fn exec(
+    llm_router_table: &LLMRouterTable,
+    provider: Option<String>,
+    refresh: bool,
+) -> anyhow::Result<()> {
+    let mut providers: Vec<_> = llm_router_table
+        .providers
+        .iter()
+        .filter(|(id, _)| provider.as_ref().is_none_or(|provider| provider == *id))
+        .collect();
+    if providers.is_empty() {
+        let provider = provider.unwrap_or_default();
+        return Err(anyhow!(
+            "{provider} is not configured. Run `minion login {provider}` first."
+        ));
+    }
+    providers.sort_by_key(|(id, _)| id.as_str());
+
+    let listings = join_all(providers.iter().map(|(_, details)| {
+        models::list(
+            details.provider,
+            &details.api_key,
+            &details.settings,
+            refresh,
+        )
+    }))
+    .await;
+
+    for ((id, details), listing) in providers.into_iter().zip(listings) {
+        let default = if *id == llm_router_table.default_provider {
+            " (default provider)"
+        } else {
+            ""
+        };
+        println!("{}{default}", details.provider.display_name());
+
+        let mut models = match listing {
+            Ok(models) => models,
+            Err(err) => {
+                println!("  Failed to list models: {err}");
+                continue;
+            }
+        };
+        models.sort_by(|a, b| a.id.cmp(&b.id));
+        for model in models {
+            let aliases = aliases_for(llm_router_table, id, &model.id);
+            let aliases = if aliases.is_empty() {
+                String::new()
+            } else {
+                format!("  ← {}", aliases.join(", "))
+            };
+            println!("  {id}/{:<48}{}{aliases}", model.id, metadata(&model));
+        }
+    }
+    Ok(())
+}
+
+/// Aliases that resolve to the given model of the given provider.
+fn aliases_for(llm_router_table: &LLMRouterTable, provider_id: &str, model: &str) -> Vec<String> {
+    let mut aliases: Vec<_> = llm_router_table
+        .aliases
+        .keys()
+        .filter(|alias| {
+            let (model_name, details) = llm_router_table.details_for_model(alias);
+            details.provider.id() == provider_id && model_name == model
+        })
+        .cloned()
+        .collect();
+    aliases.sort();
+    aliases
+}
+
+/// Context window and pricing in a compact form, e.g. `128k ctx  $0.59/$0.79 per 1M`.
+fn metadata(model: &ModelInfo) -> String {
+    let mut parts = Vec::new();
+    if let Some(context_window) = model.context_window {
+        parts.push(format!("{}k ctx", context_window / 1000));
+    }
+    if let (Some(prompt), Some(completion)) = (model.prompt_price, model.completion_price) {
+        parts.push(format!(
+            "${:.2}/${:.2} per 1M",
+            prompt * 1_000_000.0,
+            completion * 1_000_000.0
+        ));
+    }
+    parts.join("  ")
+}
@@ -1,21 +1,30 @@
 use anyhow::anyhow;
 use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
 use url::Url;
 use uuid::Uuid;
 
 use crate::{
     api::TaskOutcome,
-    config::LLMRouterTable,
+    artifacts::ArtifactStore,
+    cache::{self, ResponseCache},
+    config::{LLMRouterTable, RunSettings},
     context::{self, Context},
+    event::{EventBus, EventKind},
+    metrics::Metrics,
+    models,
+    ratelimit::RateLimits,
     runtime::ContainerConfig,
+    status::StatusLine,
+    usage::UsageTracker,
 };
 
 const AGENT_CONTAINER_IMAGE: &str = "ghcr.io/autominion/default-minion:x86-64-latest";
 
 pub async fn run<P: AsRef<Path>>(
     llm_router_table: LLMRouterTable,
-    containerfile: &Option<P>,
-    nested: bool,
+    settings: &RunSettings,
     path: &P,
     task_description: String,
 ) -> anyhow::Result<()> {
@@ -30,43 +39,99 @@ pub async fn run<P: AsRef<Path>>(
     let minion_api_base_url = format!("http://host.docker.internal:{agent_api_port}/api/");
     let fork_branch = Uuid::now_v7().to_string();
     let agent_api_key = context::random_key();
+    let observer_api_key = context::random_key();
     let host_address = format!("http://{agent_api_host}:{agent_api_port}");
 
     let base_branch = current_branch_name(path)?;
 
     create_git_branch(path, &fork_branch)?;
 
+    let status = Arc::new(StatusLine::new());
+    let events = Arc::new(EventBus::new());
+    let artifacts = Arc::new(ArtifactStore::new(
+        crate::runs::run_dir(&fork_branch)?.join("artifacts"),
+    ));
+
+    let response_cache = if settings.cache.unwrap_or(false) {
+        Some(Arc::new(ResponseCache::new(
+            ResponseCache::default_dir()?,
+            settings
+                .cache_ttl
+                .map(Duration::from_secs)
+                .unwrap_or(cache::DEFAULT_TTL),
+            settings
+                .cache_max_size_bytes()?
+                .map(|size| size as u64)
+                .unwrap_or(cache::DEFAULT_MAX_SIZE),
+        )))
+    } else {
+        None
+    };
+
+    let catalog = models::catalog(&llm_router_table);
+    let context_windows =
+        models::context_windows(&catalog, &llm_router_table, &settings.context_windows);
+    let usage = Arc::new(UsageTracker::new(&catalog));
+    let metrics = Metrics::new()?;
+
     let ctx = Context {
+        rate_limits: RateLimits::new(&llm_router_table),
         llm_router_table,
         agent_api_key: agent_api_key.clone(),
-        task_description,
+        observer_api_key: observer_api_key.clone(),
+        task_description: full_task_description(task_description, settings),
         git_user_name: "minion[bot]".to_owned(),
         git_user_email: "minion@localhost".to_owned(),
         git_repo_url,
         git_branch: fork_branch.clone(),
         git_repo_path: path.as_ref().to_path_buf(),
+        status: status.clone(),
+        events: events.clone(),
+        metrics: metrics.clone(),
+        artifacts: artifacts.clone(),
+        response_cache: response_cache.clone(),
+        context_windows,
+        context_strategy: settings.context_strategy.unwrap_or_default(),
+        policy: settings.policy.clone(),
+        usage: usage.clone(),
     };
 
-    let image = if let Some(containerfile) = containerfile {
-        rt.build_container_image(containerfile).await?
+    let image = if let Some(containerfile) = &settings.containerfile {
+        rt.build_container_image(path.as_ref().join(containerfile))
+            .await?
     } else {
-        rt.pull_container_image(AGENT_CONTAINER_IMAGE).await?;
-        AGENT_CONTAINER_IMAGE.to_owned()
+        let image = settings.image.as_deref().unwrap_or(AGENT_CONTAINER_IMAGE);
+        rt.pull_container_image(image).await?;
+        image.to_owned()
     };
 
+    let mut env_vars = vec![
+        ("MINION_API_BASE_URL".to_owned(), minion_api_base_url),
+        ("MINION_API_TOKEN".to_owned(), agent_api_key),
+    ];
+    if let Some(verify_command) = &settings.verify_command {
+        env_vars.push(("MINION_VERIFY_COMMAND".to_owned(), verify_command.clone()));
+    }
+
     let container_config = ContainerConfig {
-        image,
-        env_vars: vec![
-            ("MINION_API_BASE_URL".to_owned(), minion_api_base_url),
-            ("MINION_API_TOKEN".to_owned(), agent_api_key),
-        ],
-        nested,
+        image: image.clone(),
+        env_vars,
+        nested: settings.nested.unwrap_or(false),
+        memory: settings.memory_bytes()?,
+        cpus: settings.cpus,
     };
 
     let server = tokio::spawn(crate::api::run_server(listener, ctx));
     // Wait for the server to be ready by polling the /ready endpoint
     crate::api::wait_until_ready(&host_address).await?;
 
+    println!("Run events are streamed at {host_address}/events");
+    println!("Run metrics are served at {host_address}/metrics");
+    println!("Observer token: {observer_api_key}");
+    println!();
+
+    let ticker = tokio::spawn(status.clone().tick());
+
     let (task_outcome, container_id) = tokio::try_join!(
         async {
             server
@@ -75,19 +140,83 @@ pub async fn run<P: AsRef<Path>>(
                 .map_err(|e| anyhow!(e))
         },
         async {
-            rt.run_container(container_config)
-                .await
-                .map_err(|e| anyhow!(e))
+            rt.run_container(container_config, status.clone(), || {
+                events.emit(EventKind::ContainerStarted { image });
+            })
+            .await
+            .map_err(|e| anyhow!(e))
         }
     )?;
 
+    ticker.abort();
+    status.finish();
+
     rt.delete_container(container_id.to_string()).await?;
 
-    if task_outcome == TaskOutcome::Failure {
+    if task_outcome != TaskOutcome::Failure {
+        squash_merge_branch(path, &base_branch, &fork_branch)?;
+    }
+
+    // The agent's work is merged at this point, so failing to report on the run mustn't
+    // fail it.
+    if let Err(err) = print_artifacts(&artifacts) {
+        log::warn!("Failed to list the artifacts of the run: {err}");
+    }
+    if let Err(err) = print_usage(&usage, &fork_branch) {
+        log::warn!("Failed to store the LLM usage of the run: {err}");
+    }
+    if response_cache.is_some() {
+        let lookups = |result| metrics.response_cache.with_label_values(&[result]).get();
+        println!();
+        println!(
+            "Response cache: {} hit(s), {} miss(es)",
+            lookups("hit"),
+            lookups("miss")
+        );
+    }
+    Ok(())
+}
+
+/// Append the configured instructions to the user's task description.
+fn full_task_description(task_description: String, settings: &RunSettings) -> String {
+    let mut description = task_description;
+    if let Some(instructions) = &settings.instructions {
+        description.push_str("\n\n");
+        description.push_str(instructions.trim());
+    }
+    if let Some(verify_command) = &settings.verify_command {
+        description.push_str(&format!(
+            "\n\nBefore completing the task, verify your changes by running `{verify_command}`."
+        ));
+    }
+    description
+}
+
+/// Print the LLM usage of the run and store it in the run record.
+fn print_usage(usage: &UsageTracker, run_id: &str) -> anyhow::Result<()> {
+    let usage = usage.snapshot();
+    if !usage.models.is_empty() {
+        println!();
+        println!("LLM usage:");
+        super::usage::print_table(&usage.models);
+    }
+    usage.save(&crate::runs::run_dir(run_id)?)
+}
+
+/// Print the artifacts uploaded by the agent, if any.
+fn print_artifacts(artifacts: &ArtifactStore) -> anyhow::Result<()> {
+    let list = artifacts.list()?;
+    if list.is_empty() {
         return Ok(());
     }
 
-    squash_merge_branch(path, &base_branch, &fork_branch)?;
+    println!();
+    println!("The agent stored {} artifact(s) in:", list.len());
+    println!("{}", artifacts.dir().to_string_lossy());
+    for (path, size) in list {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        println!("  {name} ({size} bytes)");
+    }
     Ok(())
 }
 
@@ -0,0 +1,67 @@
+use std::collections::BTreeMap;
+
+use crate::status::format_count;
+use crate::usage::{self, ModelUsage, RunUsage};
+
+/// Print the total usage of the runs started within the last `days` days.
+pub fn exec(days: u64) -> anyhow::Result<()> {
+    let since = usage::now().saturating_sub(days.saturating_mul(24 * 60 * 60));
+    let runs = RunUsage::load_since(since)?;
+    let period = if days == 1 {
+        "day".to_owned()
+    } else {
+        format!("{days} days")
+    };
+    if runs.is_empty() {
+        println!("No runs in the last {period}.");
+        return Ok(());
+    }
+    println!("Usage of {} run(s) in the last {period}:", runs.len());
+    println!();
+    print_table(&RunUsage::total(&runs));
+    Ok(())
+}
+
+/// Print usage by model with a total row.
+pub fn print_table(models: &BTreeMap<String, ModelUsage>) {
+    let width = models
+        .keys()
+        .map(|model| model.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("Model".len());
+    println!(
+        "{:<width$}  {:>8}  {:>8}  {:>10}  {:>11}  {:>10}",
+        "Model", "Requests", "Prompt", "Completion", "Avg latency", "Cost"
+    );
+    let mut total = ModelUsage::default();
+    for (model, usage) in models {
+        print_row(model, usage, width);
+        total.add(usage);
+    }
+    if models.len() > 1 {
+        print_row("Total", &total, width);
+    }
+    if total.partial_cost {
+        println!();
+        println!("Costs marked with + are lower bounds, as the prices of some models are unknown.");
+    }
+}
+
+fn print_row(name: &str, usage: &ModelUsage, width: usize) {
+    let latency = usage
+        .average_latency()
+        .map(|latency| format!("{:.1}s", latency.as_secs_f64()))
+        .unwrap_or("-".to_owned());
+    let cost = match usage.cost {
+        Some(cost) if usage.partial_cost => format!("${cost:.4}+"),
+        Some(cost) => format!("${cost:.4}"),
+        None => "-".to_owned(),
+    };
+    println!(
+        "{name:<width$}  {:>8}  {:>8}  {:>10}  {latency:>11}  {cost:>10}",
+        usage.requests,
+        format_count(usage.prompt_tokens),
+        format_count(usage.completion_tokens),
+    );
+}
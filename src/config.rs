@@ -1,83 +1,309 @@
-use core::fmt;
-use std::path::PathBuf;
-use std::{collections::HashMap, fs};
+use std::path::{Path, PathBuf};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+};
 
 use anyhow::anyhow;
-use once_cell::sync::Lazy;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use url::Url;
 
-static OPENROUTER_CHAT_COMPLETIONS_URL: Lazy<Url> = Lazy::new(|| {
-    Url::parse("https://openrouter.ai/api/v1/chat/completions")
-        .expect("Failed to parse OpenRouter chat completions URL")
-});
+use crate::providers::{self, Provider};
+use crate::secrets::{self, SecretStorage};
 
-static GROQ_CHAT_COMPLETIONS_URL: Lazy<Url> = Lazy::new(|| {
-    Url::parse("https://api.groq.com/openai/v1/chat/completions")
-        .expect("Failed to parse Groq chat completions URL")
-});
+/// Name of the per-repository configuration file.
+pub const PROJECT_CONFIG_FILENAME: &str = ".minion.toml";
 
-static GEMINI_CHAT_COMPLETIONS_URL: Lazy<Url> = Lazy::new(|| {
-    Url::parse("https://generativelanguage.googleapis.com/v1beta/openai/chat/completions")
-        .expect("Failed to parse Gemini chat completions URL")
-});
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Config {
+    /// The profile used if no other profile is selected.
+    /// Its settings are stored at the top level of the config file.
+    #[serde(flatten)]
+    pub default_profile: Profile,
+    /// Named profiles, stored in `[profiles.<name>]` sections.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Profile>,
+    /// Where API keys are stored when the config is saved.
+    /// If unset, the system keyring is used when available.
+    pub secret_storage: Option<SecretStorage>,
+    /// Name of the profile selected for this invocation, if any.
+    #[serde(skip)]
+    pub selected_profile: Option<String>,
+}
 
-static COHERE_CHAT_COMPLETIONS_URL: Lazy<Url> = Lazy::new(|| {
-    Url::parse("https://api.cohere.ai/compatibility/v1/chat/completions")
-        .expect("Failed to parse Cohere chat completions URL")
-});
+/// A set of LLM provider keys and settings.
+///
+/// API keys are stored in the `[keys]` table by provider ID and hold either the key itself or
+/// a reference to a secret store, see [`crate::secrets`].
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Profile {
+    /// ID of the provider used for requests that don't name one.
+    pub llm_provider: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub keys: BTreeMap<String, String>,
+    /// Provider-specific settings, stored in `[providers.<id>]` sections.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub providers: BTreeMap<String, ProviderSettings>,
+    #[serde(default)]
+    pub run: RunSettings,
+}
 
+/// Settings of a provider. The rate limits apply to all providers, the other settings are
+/// specific to the provider and interpreted by it, see [`Provider::check_settings`].
 #[derive(Clone, Default, Serialize, Deserialize)]
-pub struct Config {
-    pub llm_provider: Option<LLMProvider>,
-    pub openrouter_key: Option<String>,
-    pub groq_key: Option<String>,
-    pub google_gemini_key: Option<String>,
-    pub cohere_key: Option<String>,
+pub struct ProviderSettings {
+    /// Maximum number of requests per minute.
+    pub requests_per_minute: Option<usize>,
+    /// Maximum number of tokens per minute, counting estimated prompt and reported
+    /// completion tokens.
+    pub tokens_per_minute: Option<u64>,
+    /// Maximum number of requests in flight at the same time.
+    pub max_concurrent: Option<usize>,
+    /// Provider-specific settings.
+    #[serde(flatten)]
+    pub options: toml::Table,
+}
+
+impl ProviderSettings {
+    /// The provider-specific settings as the provider's settings type.
+    pub fn parse_options<T: DeserializeOwned>(&self) -> anyhow::Result<T> {
+        Ok(toml::Value::Table(self.options.clone()).try_into()?)
+    }
+}
+
+impl Profile {
+    /// The configured API key (or secret reference) for the given provider.
+    pub fn key(&self, provider: &dyn Provider) -> Option<&String> {
+        self.keys.get(provider.id())
+    }
+
+    /// The first configured provider, in registry order.
+    pub fn first_configured_provider(&self) -> Option<&'static dyn Provider> {
+        providers::PROVIDERS
+            .iter()
+            .copied()
+            .find(|provider| self.key(*provider).is_some())
+    }
+}
+
+/// Settings for running agents.
+///
+/// These can be set in the `[run]` section of the user's config or of a profile, and in a
+/// `.minion.toml` file at the root of a repository, which takes precedence.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct RunSettings {
+    /// Container image of the agent.
+    pub image: Option<String>,
+    /// Containerfile to build the agent image from, relative to the repository root.
+    pub containerfile: Option<PathBuf>,
+    /// Expose a Docker socket to the container.
+    pub nested: Option<bool>,
+    /// Model aliases, e.g. `default = "groq/llama-3.3-70b-versatile"`.
+    /// The `default` alias is used for requests that don't specify a model.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub models: HashMap<String, String>,
+    /// Command the agent should run to verify its changes.
+    pub verify_command: Option<String>,
+    /// Memory limit of the container, e.g. "4g".
+    pub memory: Option<String>,
+    /// Number of CPUs available to the container.
+    pub cpus: Option<f64>,
+    /// Additional instructions appended to the task description.
+    pub instructions: Option<String>,
+    /// Answer identical chat completion requests from an on-disk cache.
+    pub cache: Option<bool>,
+    /// Seconds after which cached responses expire.
+    pub cache_ttl: Option<u64>,
+    /// Maximum total size of cached responses, e.g. "500m".
+    pub cache_max_size: Option<String>,
+    /// Context window sizes in tokens by model, e.g. `"groq/llama-3.3-70b-versatile" = 131072`.
+    /// These take precedence over the sizes reported by the providers.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub context_windows: HashMap<String, u64>,
+    /// What to do with requests that don't fit the context window of the model.
+    pub context_strategy: Option<ContextStrategy>,
+    /// Rules applied to the agent's requests to LLMs.
+    #[serde(default, skip_serializing_if = "RequestPolicy::is_empty")]
+    pub policy: RequestPolicy,
 }
 
-#[derive(clap::ValueEnum, Clone, Debug, Deserialize, Serialize)]
-pub enum LLMProvider {
-    #[serde(rename = "openrouter")]
-    #[clap(name = "openrouter")]
-    OpenRouter,
-    #[serde(rename = "groq")]
-    Groq,
-    #[serde(rename = "google-gemini")]
-    GoogleGemini,
-    #[serde(rename = "cohere")]
-    Cohere,
+/// Rules applied by the chat proxy to every request of the agent, regardless of the agent's
+/// image. The policies of the user config and the repository are combined.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct RequestPolicy {
+    /// Instructions placed before the agent's system prompt.
+    pub system_prepend: Option<String>,
+    /// Instructions placed after the agent's system prompt.
+    pub system_append: Option<String>,
+    /// Upper limit for `max_tokens` and `max_completion_tokens`.
+    pub max_tokens: Option<u64>,
+    /// Request parameters removed before forwarding, e.g. `["temperature", "logit_bias"]`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub strip_params: Vec<String>,
+    /// Models the agent may not use, as `provider/model`. A trailing `*` matches any suffix,
+    /// e.g. `"openai/o1*"` or `"openrouter/*"`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deny_models: Vec<String>,
 }
 
-impl LLMProvider {
-    pub fn tag(&self) -> &'static str {
-        match self {
-            LLMProvider::OpenRouter => "openrouter",
-            LLMProvider::Groq => "groq",
-            LLMProvider::GoogleGemini => "google-gemini",
-            LLMProvider::Cohere => "cohere",
+impl RequestPolicy {
+    /// Combine two policies. Instructions and lists are concatenated, and the lower token
+    /// limit applies.
+    pub fn merge(mut self, other: RequestPolicy) -> RequestPolicy {
+        self.strip_params.extend(other.strip_params);
+        self.deny_models.extend(other.deny_models);
+        RequestPolicy {
+            system_prepend: join_instructions(self.system_prepend, other.system_prepend),
+            system_append: join_instructions(self.system_append, other.system_append),
+            max_tokens: match (self.max_tokens, other.max_tokens) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => b.or(a),
+            },
+            strip_params: self.strip_params,
+            deny_models: self.deny_models,
         }
     }
+
+    fn is_empty(&self) -> bool {
+        self.system_prepend.is_none()
+            && self.system_append.is_none()
+            && self.max_tokens.is_none()
+            && self.strip_params.is_empty()
+            && self.deny_models.is_empty()
+    }
+
+    /// Whether the policy denies the upstream model (`provider/model`).
+    pub fn denies(&self, upstream_model: &str) -> bool {
+        self.deny_models
+            .iter()
+            .any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => upstream_model.starts_with(prefix),
+                None => upstream_model == pattern,
+            })
+    }
+}
+
+/// Join two sets of instructions, separated by a blank line.
+fn join_instructions(a: Option<String>, b: Option<String>) -> Option<String> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(format!("{a}\n\n{b}")),
+        (a, b) => b.or(a),
+    }
+}
+
+/// How the chat proxy handles requests that exceed the context window of the model.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ContextStrategy {
+    /// Reject the request with a `context_length_exceeded` error.
+    #[default]
+    Error,
+    /// Replace the oldest tool outputs with a placeholder.
+    DropToolOutputs,
+    /// Drop the oldest tool outputs, then shorten the longest messages.
+    Truncate,
 }
 
-impl fmt::Display for LLMProvider {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            LLMProvider::OpenRouter => write!(f, "OpenRouter"),
-            LLMProvider::Groq => write!(f, "Groq"),
-            LLMProvider::GoogleGemini => write!(f, "Google Gemini"),
-            LLMProvider::Cohere => write!(f, "Cohere"),
+impl RunSettings {
+    /// Load the settings from the `.minion.toml` file in the given repository, if present.
+    pub fn load_project<P: AsRef<Path>>(repo_path: P) -> anyhow::Result<Option<Self>> {
+        let path = repo_path.as_ref().join(PROJECT_CONFIG_FILENAME);
+        if !path.exists() {
+            return Ok(None);
         }
+        let text = fs::read_to_string(&path)?;
+        let settings = toml::from_str(&text)
+            .map_err(|err| anyhow!("Failed to parse {}: {err}", path.to_string_lossy()))?;
+        Ok(Some(settings))
     }
+
+    /// Layer `other` on top of these settings. Values set in `other` take precedence.
+    pub fn merge(mut self, other: RunSettings) -> RunSettings {
+        self.models.extend(other.models);
+        self.context_windows.extend(other.context_windows);
+        RunSettings {
+            image: other.image.or(self.image),
+            containerfile: other.containerfile.or(self.containerfile),
+            nested: other.nested.or(self.nested),
+            models: self.models,
+            verify_command: other.verify_command.or(self.verify_command),
+            memory: other.memory.or(self.memory),
+            cpus: other.cpus.or(self.cpus),
+            instructions: join_instructions(self.instructions, other.instructions),
+            cache: other.cache.or(self.cache),
+            cache_ttl: other.cache_ttl.or(self.cache_ttl),
+            cache_max_size: other.cache_max_size.or(self.cache_max_size),
+            context_windows: self.context_windows,
+            context_strategy: other.context_strategy.or(self.context_strategy),
+            policy: self.policy.merge(other.policy),
+        }
+    }
+
+    /// The memory limit in bytes.
+    pub fn memory_bytes(&self) -> anyhow::Result<Option<i64>> {
+        self.memory
+            .as_deref()
+            .map(|memory| parse_size(memory).map_err(|_| anyhow!("Invalid memory limit: {memory}")))
+            .transpose()
+    }
+
+    /// The response cache size limit in bytes.
+    pub fn cache_max_size_bytes(&self) -> anyhow::Result<Option<i64>> {
+        self.cache_max_size
+            .as_deref()
+            .map(|size| parse_size(size).map_err(|_| anyhow!("Invalid cache size: {size}")))
+            .transpose()
+    }
+}
+
+/// Parse a size with an optional `k`, `m` or `g` suffix, e.g. "4g".
+fn parse_size(size: &str) -> anyhow::Result<i64> {
+    let size = size.trim().to_lowercase();
+    let (number, multiplier) = match size.char_indices().last() {
+        Some((i, 'k')) => (&size[..i], 1024),
+        Some((i, 'm')) => (&size[..i], 1024 * 1024),
+        Some((i, 'g')) => (&size[..i], 1024 * 1024 * 1024),
+        _ => (size.as_str(), 1),
+    };
+    let number: i64 = number.parse()?;
+    if number < 0 {
+        return Err(anyhow!("Negative size: {size}"));
+    }
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| anyhow!("Size too large: {size}"))
 }
 
 pub struct LLMRouterTable {
     pub default_provider: String,
     pub providers: HashMap<String, LLMProviderDetails>,
+    /// Model aliases mapping to "provider/model" strings.
+    pub aliases: HashMap<String, String>,
 }
 
 impl LLMRouterTable {
+    /// Resolve a model alias. Returns the input if it isn't an alias.
+    pub fn resolve_alias<'a>(&'a self, model: &'a str) -> &'a str {
+        self.aliases.get(model).map(String::as_str).unwrap_or(model)
+    }
+
+    /// The model used for requests that don't specify one.
+    pub fn default_model(&self) -> Option<&str> {
+        self.aliases.get("default").map(String::as_str)
+    }
+
+    /// Name of the provider that requests for the given model are routed to.
+    pub fn provider_for_model<'a>(&'a self, provider_and_model: &'a str) -> &'a str {
+        self.resolve_alias(provider_and_model)
+            .split_once('/')
+            .map(|(provider_name, _)| provider_name)
+            .filter(|provider_name| self.providers.contains_key(*provider_name))
+            .unwrap_or(&self.default_provider)
+    }
+
     pub fn details_for_model(&self, provider_and_model: &str) -> (String, &LLMProviderDetails) {
+        let provider_and_model = self.resolve_alias(provider_and_model);
         provider_and_model
             .split_once('/')
             .and_then(|(provider_name, model_name)| {
@@ -97,35 +323,212 @@ impl LLMRouterTable {
 }
 
 pub struct LLMProviderDetails {
+    pub provider: &'static dyn Provider,
     pub api_chat_completions_endpoint: Url,
     pub api_key: String,
+    pub settings: ProviderSettings,
 }
 
 impl Config {
+    /// Load the config, creating a default config file if none exists yet.
     pub fn load_or_create() -> anyhow::Result<Self> {
-        match Self::load() {
-            Ok(config) => Ok(config),
-            Err(_) => {
-                let config = Self::default();
-                config.save()?;
-                Ok(config)
-            }
+        if Self::filepath()?.exists() {
+            return Self::load();
+        }
+        let config = Self::default();
+        config.save()?;
+        Ok(config)
+    }
+
+    /// Load the config, using the default config if no config file exists.
+    /// Unlike [`Config::load_or_create`], this never writes a config file.
+    pub fn load_or_default() -> anyhow::Result<Self> {
+        if Self::filepath()?.exists() {
+            Self::load()
+        } else {
+            Ok(Self::default())
         }
     }
 
     pub fn load() -> anyhow::Result<Self> {
-        let text = fs::read_to_string(Self::filepath()?)?;
-        let config = toml::from_str(&text)?;
+        let path = Self::filepath()?;
+        let text = fs::read_to_string(&path)?;
+        let (config, unknown_keys) = Self::parse(&text).map_err(|err| {
+            anyhow!(
+                "Invalid config file at {}: {err}\nRun `minion config edit` to fix it.",
+                path.to_string_lossy()
+            )
+        })?;
+        for key in unknown_keys {
+            log::warn!("Ignoring unknown config setting `{key}`");
+        }
         Ok(config)
     }
 
+    /// Parse the text of a config file.
+    /// Returns the config and the keys in the file that are not recognized.
+    pub fn parse(text: &str) -> anyhow::Result<(Self, Vec<String>)> {
+        Self::from_table(toml::from_str(text)?)
+    }
+
+    /// Convert a TOML table to a config.
+    /// Returns the config and the keys in the table that are not recognized.
+    pub fn from_table(mut table: toml::Table) -> anyhow::Result<(Self, Vec<String>)> {
+        migrate_legacy_keys(&mut table);
+        if let Some(toml::Value::Table(profiles)) = table.get_mut("profiles") {
+            for (_, profile) in profiles.iter_mut() {
+                if let toml::Value::Table(profile) = profile {
+                    migrate_legacy_keys(profile);
+                }
+            }
+        }
+        let value = toml::Value::Table(table);
+        let config: Config = value.clone().try_into()?;
+        // Settings that survive a round trip through `Config` are known.
+        let known = toml::Value::try_from(&config)?;
+        let mut unknown_keys = Vec::new();
+        collect_unknown_keys(&value, &known, "", &mut unknown_keys);
+        // Provider-specific settings are only known to the provider.
+        let profiles = std::iter::once((String::new(), &config.default_profile)).chain(
+            config
+                .profiles
+                .iter()
+                .map(|(name, profile)| (format!("profiles.{name}."), profile)),
+        );
+        for (prefix, profile) in profiles {
+            for (id, settings) in &profile.providers {
+                let Some(provider) = providers::get(id) else {
+                    continue;
+                };
+                let prefix = format!("{prefix}providers.{id}.");
+                let keys = provider
+                    .check_settings(settings)
+                    .map_err(|err| anyhow!("Invalid setting in `{prefix}*`: {err}"))?;
+                unknown_keys.extend(keys.into_iter().map(|key| format!("{prefix}{key}")));
+            }
+        }
+        Ok((config, unknown_keys))
+    }
+
+    /// Save the config, moving any plaintext API keys into the configured secret storage.
     pub fn save(&self) -> anyhow::Result<()> {
-        let text = toml::to_string(self)?;
+        let mut config = self.clone();
+        config.store_secrets()?;
+        let text = toml::to_string(&config)?;
         fs::create_dir_all(Self::filepath()?.parent().unwrap())?;
         fs::write(Self::filepath()?, text)?;
         Ok(())
     }
 
+    /// Replace plaintext API keys with references to the configured secret storage.
+    fn store_secrets(&mut self) -> anyhow::Result<()> {
+        let storage = self.secret_storage;
+        // Secrets are named like the keys in the config file, e.g. `groq` for the default
+        // profile and `profiles/work/groq` for the profile "work", so names can't collide.
+        let profiles = std::iter::once((String::new(), &mut self.default_profile)).chain(
+            self.profiles
+                .iter_mut()
+                .map(|(name, profile)| (format!("profiles/{name}/"), profile)),
+        );
+        for (prefix, profile) in profiles {
+            for (provider_id, secret) in profile.keys.iter_mut() {
+                if secrets::SecretRef::parse(secret).is_some() {
+                    continue;
+                }
+                let name = format!("{prefix}{provider_id}");
+                let reference = match storage {
+                    Some(storage) => secrets::store_secret(storage, &name, secret)?,
+                    None => secrets::store_secret(SecretStorage::Keyring, &name, secret)
+                        .unwrap_or_else(|err| {
+                            eprintln!(
+                                "Warning: The system keyring is unavailable ({err}), so the \
+                                 `{provider_id}` API key is stored in plaintext in the config \
+                                 file. Set `secret_storage = \"vault\"` to encrypt it with a \
+                                 passphrase, or `secret_storage = \"plaintext\"` to store keys \
+                                 in plaintext without this warning."
+                            );
+                            secret.clone()
+                        }),
+                };
+                *secret = reference;
+            }
+        }
+        Ok(())
+    }
+
+    /// Use API keys from `MINION_<PROVIDER>_KEY` environment variables for the selected
+    /// profile, and the provider from `MINION_LLM_PROVIDER` as its default.
+    /// This allows running without a config file, e.g. in CI.
+    pub fn apply_env_overrides(&mut self) -> anyhow::Result<()> {
+        let profile = self.profile_mut();
+        for provider in providers::PROVIDERS {
+            let var = providers::key_env_var(*provider);
+            if std::env::var_os(&var).is_some() {
+                profile
+                    .keys
+                    .insert(provider.id().to_owned(), format!("env:{var}"));
+            }
+        }
+
+        if let Ok(id) = std::env::var("MINION_LLM_PROVIDER") {
+            let provider = providers::get(&id.to_lowercase())
+                .ok_or_else(|| anyhow!("Unknown LLM provider in MINION_LLM_PROVIDER: {id}"))?;
+            profile.llm_provider = Some(provider.id().to_owned());
+        } else if profile.llm_provider.is_none() {
+            profile.llm_provider = profile
+                .first_configured_provider()
+                .map(|provider| provider.id().to_owned());
+        }
+        Ok(())
+    }
+
+    /// Select the named profile for this invocation. `None` selects the default profile.
+    pub fn select_profile(&mut self, name: Option<String>) {
+        self.selected_profile = name;
+    }
+
+    /// Whether the selected profile exists in the config.
+    pub fn selected_profile_exists(&self) -> bool {
+        match &self.selected_profile {
+            Some(name) => self.profiles.contains_key(name),
+            None => true,
+        }
+    }
+
+    /// The selected profile. Falls back to the default profile if the selected profile
+    /// doesn't exist; check [`Config::selected_profile_exists`] first where that matters.
+    pub fn profile(&self) -> &Profile {
+        self.selected_profile
+            .as_ref()
+            .and_then(|name| self.profiles.get(name))
+            .unwrap_or(&self.default_profile)
+    }
+
+    /// The selected profile for modification. Creates the profile if it doesn't exist.
+    pub fn profile_mut(&mut self) -> &mut Profile {
+        match &self.selected_profile {
+            Some(name) => self.profiles.entry(name.clone()).or_default(),
+            None => &mut self.default_profile,
+        }
+    }
+
+    /// Human-readable description of the selected profile for messages.
+    pub fn profile_description(&self) -> String {
+        match &self.selected_profile {
+            Some(name) => format!("profile '{name}'"),
+            None => "the default profile".to_owned(),
+        }
+    }
+
+    /// Run settings of the selected profile, layered on top of those of the default profile.
+    pub fn run_settings(&self) -> RunSettings {
+        let settings = self.default_profile.run.clone();
+        match &self.selected_profile {
+            Some(_) => settings.merge(self.profile().run.clone()),
+            None => settings,
+        }
+    }
+
     pub fn filepath() -> anyhow::Result<PathBuf> {
         Ok(dirs::config_dir()
             .ok_or(anyhow!("Failed to locate appropriate config directory"))?
@@ -133,53 +536,173 @@ impl Config {
             .join("config.toml"))
     }
 
-    pub fn llm_router_table(&self) -> Option<LLMRouterTable> {
+    /// Build the router table for the selected profile, resolving API keys from secret storage.
+    /// Providers that can't be used, e.g. because a required setting is missing, are skipped
+    /// with a warning. `aliases` are the model aliases of the run settings. Returns `None` if
+    /// no default LLM provider is configured.
+    pub fn llm_router_table(
+        &self,
+        aliases: HashMap<String, String>,
+    ) -> anyhow::Result<Option<LLMRouterTable>> {
+        let profile = self.profile();
         let mut providers = HashMap::new();
+        // Providers that are misconfigured, with the reason.
+        let mut skipped = HashMap::new();
 
-        if let Some(key) = &self.openrouter_key {
+        for (id, key) in &profile.keys {
+            let Some(provider) = providers::get(id) else {
+                log::warn!("Ignoring API key of unknown LLM provider `{id}`");
+                continue;
+            };
+            let settings = profile.providers.get(id).cloned().unwrap_or_default();
+            let details = provider
+                .chat_completions_url(&settings)
+                .and_then(|endpoint| Ok((endpoint, secrets::resolve(key)?)));
+            let (api_chat_completions_endpoint, api_key) = match details {
+                Ok(details) => details,
+                Err(err) => {
+                    log::warn!("Skipping {}: {err}", provider.display_name());
+                    skipped.insert(id.as_str(), err);
+                    continue;
+                }
+            };
             providers.insert(
-                "openrouter".to_string(),
+                id.clone(),
                 LLMProviderDetails {
-                    api_chat_completions_endpoint: OPENROUTER_CHAT_COMPLETIONS_URL.clone(),
-                    api_key: key.clone(),
+                    provider,
+                    api_chat_completions_endpoint,
+                    api_key,
+                    settings,
                 },
             );
         }
-        if let Some(key) = &self.groq_key {
-            providers.insert(
-                "groq".to_string(),
-                LLMProviderDetails {
-                    api_chat_completions_endpoint: GROQ_CHAT_COMPLETIONS_URL.clone(),
-                    api_key: key.clone(),
-                },
-            );
+
+        let Some(default_llm_provider) = &profile.llm_provider else {
+            return Ok(None);
+        };
+        if let Some(err) = skipped.remove(default_llm_provider.as_str()) {
+            return Err(err.context(format!(
+                "The default LLM provider `{default_llm_provider}` can't be used"
+            )));
         }
-        if let Some(key) = &self.google_gemini_key {
-            providers.insert(
-                "google-gemini".to_string(),
-                LLMProviderDetails {
-                    api_chat_completions_endpoint: GEMINI_CHAT_COMPLETIONS_URL.clone(),
-                    api_key: key.clone(),
-                },
-            );
+
+        Ok(Some(LLMRouterTable {
+            default_provider: default_llm_provider.clone(),
+            providers,
+            aliases,
+        }))
+    }
+}
+
+/// Move API keys from the per-provider fields used by earlier versions (e.g. `groq_key`)
+/// into the `[keys]` table.
+fn migrate_legacy_keys(profile: &mut toml::Table) {
+    for provider in providers::PROVIDERS {
+        let field = format!("{}_key", provider.id().replace('-', "_"));
+        let Some(key) = profile.remove(&field) else {
+            continue;
+        };
+        let keys = profile
+            .entry("keys")
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+        if let toml::Value::Table(keys) = keys {
+            keys.entry(provider.id()).or_insert(key);
         }
-        if let Some(key) = &self.cohere_key {
-            providers.insert(
-                "cohere".to_string(),
-                LLMProviderDetails {
-                    api_chat_completions_endpoint: COHERE_CHAT_COMPLETIONS_URL.clone(),
-                    api_key: key.clone(),
-                },
-            );
+    }
+}
+
+fn collect_unknown_keys(
+    value: &toml::Value,
+    known: &toml::Value,
+    prefix: &str,
+    unknown_keys: &mut Vec<String>,
+) {
+    let (Some(table), Some(known_table)) = (value.as_table(), known.as_table()) else {
+        return;
+    };
+    for (key, value) in table {
+        let path = format!("{prefix}{key}");
+        match known_table.get(key) {
+            Some(known) => collect_unknown_keys(value, known, &format!("{path}."), unknown_keys),
+            // Empty tables are dropped when serializing and can be ignored.
+            None if value.as_table().is_some_and(|t| t.is_empty()) => {}
+            None => unknown_keys.push(path),
         }
+    }
+}
 
-        let Some(default_llm_provider) = &self.llm_provider else {
-            return None;
-        };
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        Some(LLMRouterTable {
-            default_provider: default_llm_provider.tag().to_string(),
-            providers,
-        })
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse_size("123").unwrap(), 123);
+        assert_eq!(parse_size("512k").unwrap(), 512 * 1024);
+        assert_eq!(parse_size(" 64M ").unwrap(), 64 * 1024 * 1024);
+        assert_eq!(parse_size("4g").unwrap(), 4 * 1024 * 1024 * 1024);
+    }
+
+    #[test]
+    fn rejects_invalid_sizes() {
+        for size in ["", "g", "4gb", "1.5g", "-1k", "9223372036854775807k"] {
+            assert!(parse_size(size).is_err(), "{size:?} should be rejected");
+        }
+    }
+
+    #[test]
+    fn reports_unknown_provider_settings() {
+        let (config, unknown_keys) = Config::parse(
+            r#"
+            [providers.azure]
+            resource = "example"
+            max_concurrent = 2
+            deployment = "gpt-4o"
+
+            [profiles.work.providers.groq]
+            organization = "org"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            unknown_keys,
+            [
+                "providers.azure.deployment",
+                "profiles.work.providers.groq.organization"
+            ]
+        );
+        let azure = &config.default_profile.providers["azure"];
+        assert_eq!(azure.max_concurrent, Some(2));
+        assert_eq!(azure.options["resource"].as_str(), Some("example"));
+        assert!(Config::parse("[providers.azure]\nresource = 1").is_err());
+    }
+
+    #[test]
+    fn migrates_legacy_keys() {
+        let mut profile: toml::Table = toml::from_str(
+            r#"
+            llm_provider = "openai"
+            openai_key = "legacy"
+            groq_key = "gsk-legacy"
+
+            [keys]
+            openai = "current"
+            "#,
+        )
+        .unwrap();
+        migrate_legacy_keys(&mut profile);
+
+        let expected: toml::Table = toml::from_str(
+            r#"
+            llm_provider = "openai"
+
+            [keys]
+            openai = "current"
+            groq = "gsk-legacy"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(profile, expected);
     }
 }
@@ -1,13 +1,25 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
 use rand::{distr::Alphanumeric, Rng};
 use url::Url;
 
-use crate::config::LLMRouterTable;
+use crate::artifacts::ArtifactStore;
+use crate::cache::ResponseCache;
+use crate::config::{ContextStrategy, LLMRouterTable, RequestPolicy};
+use crate::event::EventBus;
+use crate::metrics::Metrics;
+use crate::ratelimit::RateLimits;
+use crate::status::StatusLine;
+use crate::usage::UsageTracker;
 
 pub struct Context {
     /// LLM API configuration and secrets.
     pub llm_router_table: LLMRouterTable,
     /// Randomly generated key supplied to the agent.
     pub agent_api_key: String,
+    /// Randomly generated key for observers of the run's event stream.
+    pub observer_api_key: String,
     /// The user's task description.
     pub task_description: String,
     /// The git username to use for commits.
@@ -23,6 +35,26 @@ pub struct Context {
     pub git_branch: String,
     /// The path to the git repository on the host machine.
     pub git_repo_path: std::path::PathBuf,
+    /// Live status shown in the terminal during the run.
+    pub status: Arc<StatusLine>,
+    /// Events published during the run.
+    pub events: Arc<EventBus>,
+    /// Metrics exposed on the `/metrics` endpoint.
+    pub metrics: Metrics,
+    /// Files uploaded by the agent.
+    pub artifacts: Arc<ArtifactStore>,
+    /// Cache of chat completion responses, if enabled.
+    pub response_cache: Option<Arc<ResponseCache>>,
+    /// Client-side rate limits of the providers.
+    pub rate_limits: RateLimits,
+    /// Context window sizes in tokens by upstream model (`provider/model`), where known.
+    pub context_windows: HashMap<String, u64>,
+    /// How requests exceeding the context window are handled.
+    pub context_strategy: ContextStrategy,
+    /// Rules applied to the agent's requests.
+    pub policy: RequestPolicy,
+    /// LLM usage of the run.
+    pub usage: Arc<UsageTracker>,
 }
 
 /// Generate a random API key.
@@ -0,0 +1,109 @@
+//! Typed events describing what happens during a run.
+
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+/// Number of events buffered for slow subscribers before they start missing events.
+const EVENT_BUFFER_SIZE: usize = 256;
+
+/// Something that happened during a run.
+#[derive(Clone, Debug, Serialize)]
+pub struct Event {
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventKind {
+    /// The agent container was started.
+    ContainerStarted { image: String },
+    /// The agent made a request to an LLM through the chat proxy.
+    LlmInteraction {
+        model: String,
+        prompt_tokens: Option<u64>,
+        completion_tokens: Option<u64>,
+        finish_reason: Option<String>,
+        /// Whether the upstream provider failed to produce a response.
+        failed: bool,
+        /// Whether the response was answered from the response cache, without using tokens.
+        cached: bool,
+    },
+    /// The agent asked the user a question.
+    InquiryAsked { inquiry: String },
+    /// The user answered the agent's question.
+    InquiryAnswered { answer: serde_json::Value },
+    /// The agent pushed to the git repository.
+    GitPushReceived { branch: String },
+    /// The agent reported the task as completed.
+    TaskCompleted { description: String },
+    /// The agent reported the task as failed.
+    TaskFailed { description: String },
+}
+
+impl EventKind {
+    /// Name of the event type, as used in the serialized `type` field.
+    pub fn name(&self) -> &'static str {
+        match self {
+            EventKind::ContainerStarted { .. } => "container_started",
+            EventKind::LlmInteraction { .. } => "llm_interaction",
+            EventKind::InquiryAsked { .. } => "inquiry_asked",
+            EventKind::InquiryAnswered { .. } => "inquiry_answered",
+            EventKind::GitPushReceived { .. } => "git_push_received",
+            EventKind::TaskCompleted { .. } => "task_completed",
+            EventKind::TaskFailed { .. } => "task_failed",
+        }
+    }
+}
+
+/// Broadcasts run events to any number of subscribers.
+pub struct EventBus {
+    /// `None` once the bus is closed.
+    tx: Mutex<Option<broadcast::Sender<Event>>>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(EVENT_BUFFER_SIZE);
+        Self {
+            tx: Mutex::new(Some(tx)),
+        }
+    }
+
+    /// Publish an event to all current subscribers.
+    pub fn emit(&self, kind: EventKind) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        if let Some(tx) = &*self.tx.lock().expect("Event bus lock poisoned") {
+            // Sending only fails if there are no subscribers, which is fine.
+            let _ = tx.send(Event { timestamp, kind });
+        }
+    }
+
+    /// Receive all events published from now on. Subscribers of a closed bus receive none.
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        match &*self.tx.lock().expect("Event bus lock poisoned") {
+            Some(tx) => tx.subscribe(),
+            None => broadcast::channel(1).1,
+        }
+    }
+
+    /// Stop publishing events. Subscribers still receive the events published so far,
+    /// after which their streams end.
+    pub fn close(&self) {
+        self.tx.lock().expect("Event bus lock poisoned").take();
+    }
+}
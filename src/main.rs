@@ -1,9 +1,20 @@
 mod api;
+mod artifacts;
+mod cache;
 mod cli;
 mod config;
 mod context;
+mod event;
+mod metrics;
+mod models;
+mod prompt;
 mod providers;
+mod ratelimit;
+mod runs;
 mod runtime;
+mod secrets;
+mod status;
+mod usage;
 mod util;
 
 pub fn main() {
@@ -0,0 +1,97 @@
+//! Prometheus metrics collected by the minion API server.
+
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, Opts, Registry, TextEncoder,
+};
+
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    /// Chat completion requests forwarded, by provider and model.
+    pub chat_requests: IntCounterVec,
+    /// Chat completion responses from providers, by provider, model and HTTP status code.
+    pub chat_responses: IntCounterVec,
+    /// Time taken to answer chat completion requests.
+    pub chat_request_duration: Histogram,
+    /// Tokens used, by provider, model and direction ("in" or "out").
+    pub tokens: IntCounterVec,
+    /// Git proxy operations, by operation ("fetch" or "push").
+    pub git_operations: IntCounterVec,
+    /// Inquiries sent to the user.
+    pub inquiries: IntCounter,
+    /// Task outcomes reported by the agent, by outcome.
+    pub task_outcomes: IntCounterVec,
+    /// Response cache lookups, by result ("hit" or "miss").
+    pub response_cache: IntCounterVec,
+}
+
+impl Metrics {
+    pub fn new() -> prometheus::Result<Self> {
+        let registry = Registry::new_custom(Some("minion".to_owned()), None)?;
+
+        let chat_requests = IntCounterVec::new(
+            Opts::new("chat_requests_total", "Chat completion requests forwarded"),
+            &["provider", "model"],
+        )?;
+        let chat_responses = IntCounterVec::new(
+            Opts::new(
+                "chat_responses_total",
+                "Chat completion responses from providers by status code",
+            ),
+            &["provider", "model", "status"],
+        )?;
+        let chat_request_duration = Histogram::with_opts(
+            HistogramOpts::new(
+                "chat_request_duration_seconds",
+                "Time taken to answer chat completion requests",
+            )
+            .buckets(vec![0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0]),
+        )?;
+        let tokens = IntCounterVec::new(
+            Opts::new("tokens_total", "Tokens used"),
+            &["provider", "model", "direction"],
+        )?;
+        let git_operations = IntCounterVec::new(
+            Opts::new("git_operations_total", "Git proxy operations"),
+            &["operation"],
+        )?;
+        let inquiries = IntCounter::new("inquiries_total", "Inquiries sent to the user")?;
+        let task_outcomes = IntCounterVec::new(
+            Opts::new("task_outcomes_total", "Task outcomes reported by the agent"),
+            &["outcome"],
+        )?;
+
+        let response_cache = IntCounterVec::new(
+            Opts::new("response_cache_lookups_total", "Response cache lookups"),
+            &["result"],
+        )?;
+
+        registry.register(Box::new(chat_requests.clone()))?;
+        registry.register(Box::new(chat_responses.clone()))?;
+        registry.register(Box::new(chat_request_duration.clone()))?;
+        registry.register(Box::new(tokens.clone()))?;
+        registry.register(Box::new(git_operations.clone()))?;
+        registry.register(Box::new(inquiries.clone()))?;
+        registry.register(Box::new(task_outcomes.clone()))?;
+        registry.register(Box::new(response_cache.clone()))?;
+
+        Ok(Self {
+            registry,
+            chat_requests,
+            chat_responses,
+            chat_request_duration,
+            tokens,
+            git_operations,
+            inquiries,
+            task_outcomes,
+            response_cache,
+        })
+    }
+
+    /// Render all metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> prometheus::Result<String> {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8(buffer).expect("Prometheus text output is valid UTF-8"))
+    }
+}
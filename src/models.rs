@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::{LLMRouterTable, ProviderSettings};
+use crate::providers::{ModelInfo, Provider};
+
+/// How long a cached model listing is used before it is fetched again.
+const CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Time after which a refresh of a model listing in the background is given up.
+const REFRESH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A provider's model listing as stored in the cache.
+#[derive(Serialize, Deserialize)]
+struct CachedModels {
+    /// Seconds since the Unix epoch at which the listing was fetched.
+    fetched_at: u64,
+    models: Vec<ModelInfo>,
+}
+
+/// Directory in which model listings are cached, one file per provider and key.
+fn cache_dir() -> anyhow::Result<PathBuf> {
+    Ok(dirs::cache_dir()
+        .ok_or(anyhow!("Failed to locate appropriate cache directory"))?
+        .join("minion")
+        .join("models"))
+}
+
+/// Name of the cache file of the provider's model listing for the key. Keys of different
+/// accounts or profiles may have access to different models, so each key gets its own
+/// listing. The file name contains a hash of the key rather than the key itself.
+fn cache_file_name(provider: &dyn Provider, key: &str) -> String {
+    let hash = format!("{:x}", Sha256::digest(key.as_bytes()));
+    format!("{}-{}.json", provider.id(), &hash[..16])
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// The provider's models for the key from the cache, if present and not older than
+/// [`CACHE_TTL`].
+pub fn cached(provider: &dyn Provider, key: &str) -> Option<Vec<ModelInfo>> {
+    let cached = read_cache(provider, key)?;
+    is_fresh(&cached).then_some(cached.models)
+}
+
+/// The provider's cached listing for the key, whatever its age.
+fn read_cache(provider: &dyn Provider, key: &str) -> Option<CachedModels> {
+    let path = cache_dir().ok()?.join(cache_file_name(provider, key));
+    serde_json::from_slice(&fs::read(path).ok()?).ok()
+}
+
+fn is_fresh(cached: &CachedModels) -> bool {
+    now().saturating_sub(cached.fetched_at) < CACHE_TTL.as_secs()
+}
+
+/// List the provider's models, using the cache unless `refresh` is set.
+pub async fn list(
+    provider: &dyn Provider,
+    key: &str,
+    settings: &ProviderSettings,
+    refresh: bool,
+) -> anyhow::Result<Vec<ModelInfo>> {
+    if !refresh {
+        if let Some(models) = cached(provider, key) {
+            return Ok(models);
+        }
+    }
+
+    let models = provider.list_models(key, settings).await?;
+    let cached = CachedModels {
+        fetched_at: now(),
+        models,
+    };
+    let dir = cache_dir()?;
+    let written = fs::create_dir_all(&dir).and_then(|()| {
+        fs::write(
+            dir.join(cache_file_name(provider, key)),
+            serde_json::to_vec(&cached)?,
+        )
+    });
+    if let Err(err) = written {
+        log::warn!(
+            "Failed to cache the {} model listing: {err}",
+            provider.display_name()
+        );
+    }
+    Ok(cached.models)
+}
+
+/// The models of the providers in the router table by upstream model (`provider/model`),
+/// from their cached model listings, so that starting a run never waits for providers.
+/// Listings that are missing or expired are refreshed in the background for later runs;
+/// until then, the context windows and prices of their models are unknown.
+pub fn catalog(llm_router_table: &LLMRouterTable) -> HashMap<String, ModelInfo> {
+    let mut catalog = HashMap::new();
+    for details in llm_router_table.providers.values() {
+        let provider = details.provider;
+        let cached = read_cache(provider, &details.api_key);
+        if !cached.as_ref().is_some_and(is_fresh) {
+            let key = details.api_key.clone();
+            let settings = details.settings.clone();
+            tokio::spawn(async move {
+                let refreshed =
+                    tokio::time::timeout(REFRESH_TIMEOUT, list(provider, &key, &settings, true))
+                        .await;
+                match refreshed {
+                    Ok(Ok(_)) => {}
+                    Ok(Err(err)) => log::debug!(
+                        "Failed to refresh the {} model listing: {err}",
+                        provider.display_name()
+                    ),
+                    Err(_) => log::debug!(
+                        "Timed out refreshing the {} model listing",
+                        provider.display_name()
+                    ),
+                }
+            });
+        }
+        for model in cached.into_iter().flat_map(|cached| cached.models) {
+            catalog.insert(format!("{}/{}", provider.id(), model.id), model);
+        }
+    }
+    catalog
+}
+
+/// Context window sizes by upstream model (`provider/model`) from the catalog.
+/// `overrides` are keyed by model or alias and take precedence.
+pub fn context_windows(
+    catalog: &HashMap<String, ModelInfo>,
+    llm_router_table: &LLMRouterTable,
+    overrides: &HashMap<String, u64>,
+) -> HashMap<String, u64> {
+    let mut context_windows: HashMap<String, u64> = catalog
+        .iter()
+        .filter_map(|(model, info)| Some((model.clone(), info.context_window?)))
+        .collect();
+    for (model, &context_window) in overrides {
+        let (model_name, details) = llm_router_table.details_for_model(model);
+        context_windows.insert(
+            format!("{}/{model_name}", details.provider.id()),
+            context_window,
+        );
+    }
+    context_windows
+}
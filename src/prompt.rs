@@ -0,0 +1,109 @@
+//! Simple blocking terminal prompts.
+
+use std::io::{self, Write};
+
+/// Read a single trimmed line from stdin after printing `prompt`.
+/// Fails with [`io::ErrorKind::UnexpectedEof`] if stdin is closed.
+pub fn read_line(prompt: &str) -> io::Result<String> {
+    print!("{prompt}");
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    if io::stdin().read_line(&mut input)? == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "stdin was closed before an answer was given",
+        ));
+    }
+    Ok(input.trim().to_string())
+}
+
+/// Ask for free text, falling back to `default` on empty input.
+pub fn text(default: Option<&str>) -> io::Result<String> {
+    let prompt = match default {
+        Some(default) => format!("Your answer [{default}]: "),
+        None => "Your answer: ".to_string(),
+    };
+    let input = read_line(&prompt)?;
+    match default {
+        Some(default) if input.is_empty() => Ok(default.to_string()),
+        _ => Ok(input),
+    }
+}
+
+/// Ask a yes/no question until a valid answer is given.
+pub fn confirm(default: Option<bool>) -> io::Result<bool> {
+    let hint = match default {
+        Some(true) => "[Y/n]",
+        Some(false) => "[y/N]",
+        None => "[y/n]",
+    };
+    loop {
+        let input = read_line(&format!("Your answer {hint}: "))?.to_lowercase();
+        match (input.as_str(), default) {
+            ("y" | "yes", _) => return Ok(true),
+            ("n" | "no", _) => return Ok(false),
+            ("", Some(default)) => return Ok(default),
+            _ => println!("Please answer 'y' or 'n'."),
+        }
+    }
+}
+
+/// Let the user pick one of `options` by number. Returns the selected index.
+pub fn select(options: &[String], default: Option<usize>) -> io::Result<usize> {
+    for (i, option) in options.iter().enumerate() {
+        println!("  {}) {option}", i + 1);
+    }
+    let prompt = match default {
+        Some(default) => format!("Select an option [{}]: ", default + 1),
+        None => "Select an option: ".to_string(),
+    };
+    loop {
+        let input = read_line(&prompt)?;
+        if input.is_empty() {
+            if let Some(default) = default {
+                return Ok(default);
+            }
+        }
+        match input.parse::<usize>() {
+            Ok(n) if (1..=options.len()).contains(&n) => return Ok(n - 1),
+            _ => println!("Please enter a number between 1 and {}.", options.len()),
+        }
+    }
+}
+
+/// Let the user pick any number of `options` as a comma-separated list of numbers.
+/// Empty input selects `default`, or nothing if there is no default.
+/// Returns the selected indices in ascending order.
+pub fn multi_select(options: &[String], default: Option<usize>) -> io::Result<Vec<usize>> {
+    for (i, option) in options.iter().enumerate() {
+        println!("  {}) {option}", i + 1);
+    }
+    let prompt = match default {
+        Some(default) => format!("Select options (comma-separated) [{}]: ", default + 1),
+        None => "Select options (comma-separated, empty for none): ".to_string(),
+    };
+    loop {
+        let input = read_line(&prompt)?;
+        if input.is_empty() {
+            return Ok(default.into_iter().collect());
+        }
+        let selected: Result<Vec<usize>, _> = input
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| match s.parse::<usize>() {
+                Ok(n) if (1..=options.len()).contains(&n) => Ok(n - 1),
+                _ => Err(()),
+            })
+            .collect();
+        match selected {
+            Ok(mut selected) => {
+                selected.sort_unstable();
+                selected.dedup();
+                return Ok(selected);
+            }
+            Err(()) => println!("Please enter numbers between 1 and {}.", options.len()),
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use std::collections::BTreeMap;
+
+use anyhow::anyhow;
+use futures::future::BoxFuture;
+use futures::FutureExt as _;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use super::{ModelInfo, Provider, Upstream};
+use crate::config::{LLMProviderDetails, ProviderSettings};
+
+const AZURE_PORTAL_URL: &str =
+    "https://portal.azure.com/#view/Microsoft_Azure_ProjectOxford/CognitiveServicesHub/~/OpenAI";
+
+/// API version used if none is configured.
+const DEFAULT_API_VERSION: &str = "2024-10-21";
+
+pub struct Azure;
+
+/// Settings in `[providers.azure]`.
+#[derive(Default, Serialize, Deserialize)]
+struct Settings {
+    /// Resource name, i.e. the `<resource>` in `<resource>.openai.azure.com`.
+    resource: Option<String>,
+    /// API version, sent as the `api-version` query parameter.
+    api_version: Option<String>,
+    /// Deployments by model name, e.g. `gpt-4o = "my-gpt-4o"`. Models without an entry are
+    /// sent to a deployment of the same name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    deployments: BTreeMap<String, String>,
+}
+
+impl Settings {
+    fn api_version(&self) -> &str {
+        self.api_version.as_deref().unwrap_or(DEFAULT_API_VERSION)
+    }
+}
+
+impl Provider for Azure {
+    fn id(&self) -> &'static str {
+        "azure"
+    }
+
+    fn display_name(&self) -> &'static str {
+        "Azure OpenAI"
+    }
+
+    fn check_settings(&self, settings: &ProviderSettings) -> anyhow::Result<Vec<String>> {
+        super::check_settings::<Settings>(settings)
+    }
+
+    /// The resource's deployments endpoint. The deployment is appended per request.
+    fn chat_completions_url(&self, settings: &ProviderSettings) -> anyhow::Result<Url> {
+        let settings: Settings = settings.parse_options()?;
+        let Some(resource) = &settings.resource else {
+            return Err(anyhow!(
+                "No Azure OpenAI resource configured. \
+                 Run `minion config set providers.azure.resource <name>`."
+            ));
+        };
+        Url::parse(&format!(
+            "https://{resource}.openai.azure.com/openai/deployments/"
+        ))
+        .map_err(|err| anyhow!("Invalid Azure OpenAI resource name `{resource}`: {err}"))
+    }
+
+    /// The deployment name doesn't tell which model is deployed, and `max_completion_tokens`
+    /// is accepted by all of them while `max_tokens` is not accepted by reasoning models.
+    fn max_tokens_param(&self) -> &'static str {
+        "max_completion_tokens"
+    }
+
+    fn acquire_key(&self) -> BoxFuture<'_, anyhow::Result<String>> {
+        super::paste_key_flow(
+            self.display_name(),
+            AZURE_PORTAL_URL,
+            &[
+                "Open your Azure OpenAI resource and copy a key from \"Keys and Endpoint\".",
+                "Afterwards, set the resource name and deployments with `minion config set`,",
+                "e.g. `providers.azure.resource` and `providers.azure.deployments.gpt-4o`.",
+            ],
+        )
+        .boxed()
+    }
+
+    /// Check the API key by listing the models available to the resource.
+    fn verify_key<'a>(
+        &'a self,
+        key: &'a str,
+        settings: &'a ProviderSettings,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        async move {
+            let mut url = self
+                .chat_completions_url(settings)?
+                .join("../models")
+                .expect("Failed to build Azure OpenAI models URL");
+            let settings: Settings = settings.parse_options()?;
+            url.query_pairs_mut()
+                .append_pair("api-version", settings.api_version());
+            let response = reqwest::Client::new()
+                .get(url)
+                .header("api-key", key)
+                .send()
+                .await?;
+            match response.status() {
+                status if status.is_success() => Ok(()),
+                status @ (reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN) => {
+                    Err(anyhow!("Key rejected ({status})"))
+                }
+                status => Err(anyhow!("Unexpected response ({status})")),
+            }
+        }
+        .boxed()
+    }
+
+    /// Azure OpenAI serves models through deployments, so the configured deployments are
+    /// the models that can be used.
+    fn list_models<'a>(
+        &'a self,
+        _key: &'a str,
+        settings: &'a ProviderSettings,
+    ) -> BoxFuture<'a, anyhow::Result<Vec<ModelInfo>>> {
+        async move {
+            let settings: Settings = settings.parse_options()?;
+            if settings.deployments.is_empty() {
+                return Err(anyhow!(
+                    "No Azure OpenAI deployments configured. \
+                     Run `minion config set providers.azure.deployments.<model> <deployment>`."
+                ));
+            }
+            Ok(settings
+                .deployments
+                .keys()
+                .map(|model| ModelInfo {
+                    id: model.clone(),
+                    context_window: None,
+                    prompt_price: None,
+                    completion_price: None,
+                })
+                .collect())
+        }
+        .boxed()
+    }
+
+    /// Route the request to the model's deployment and authenticate with the `api-key`
+    /// header instead of a bearer token.
+    fn chat_upstream(&self, details: &LLMProviderDetails, model: &str) -> anyhow::Result<Upstream> {
+        let settings: Settings = details.settings.parse_options()?;
+        let url = deployment_url(
+            &details.api_chat_completions_endpoint,
+            &settings,
+            model,
+            "chat/completions",
+        )?;
+        Ok(Upstream {
+            url,
+            model: model.to_owned(),
+            headers: vec![("api-key", details.api_key.clone())],
+        })
+    }
+
+    fn embeddings_upstream(
+        &self,
+        details: &LLMProviderDetails,
+        model: &str,
+    ) -> anyhow::Result<Upstream> {
+        let settings: Settings = details.settings.parse_options()?;
+        let url = deployment_url(
+            &details.api_chat_completions_endpoint,
+            &settings,
+            model,
+            "embeddings",
+        )?;
+        Ok(Upstream {
+            url,
+            model: model.to_owned(),
+            headers: vec![("api-key", details.api_key.clone())],
+        })
+    }
+}
+
+/// URL of an endpoint of the model's deployment. The deployment name, which may come from
+/// the agent, is added as a single path segment, so it can't change the host or path.
+fn deployment_url(
+    deployments_url: &Url,
+    settings: &Settings,
+    model: &str,
+    endpoint: &str,
+) -> anyhow::Result<Url> {
+    let deployment = settings
+        .deployments
+        .get(model)
+        .map(String::as_str)
+        .unwrap_or(model);
+    // Not a deployment name, and dropped rather than encoded as a path segment.
+    if matches!(deployment, "" | "." | "..") {
+        return Err(anyhow!("Invalid Azure OpenAI deployment `{deployment}`"));
+    }
+    let mut url = deployments_url.clone();
+    url.path_segments_mut()
+        .expect("Azure OpenAI URLs have a path")
+        .pop_if_empty()
+        .push(deployment)
+        .extend(endpoint.split('/'));
+    url.query_pairs_mut()
+        .append_pair("api-version", settings.api_version());
+    Ok(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deployment_stays_on_the_resource() {
+        let deployments_url =
+            Url::parse("https://example.openai.azure.com/openai/deployments/").unwrap();
+        let settings = Settings::default();
+        for model in [
+            "gpt-4o",
+            "../../../other",
+            "//evil.example.com/x",
+            "x?api-version=1#",
+            "@evil.example.com",
+        ] {
+            let url =
+                deployment_url(&deployments_url, &settings, model, "chat/completions").unwrap();
+            assert_eq!(url.host_str(), Some("example.openai.azure.com"), "{model}");
+            let segments: Vec<_> = url.path_segments().unwrap().collect();
+            assert_eq!(segments.len(), 5, "{model}: {url}");
+            assert_eq!(segments[..2], ["openai", "deployments"], "{model}: {url}");
+            assert_eq!(segments[3..], ["chat", "completions"], "{model}: {url}");
+            assert_eq!(
+                url.query(),
+                Some("api-version=2024-10-21"),
+                "{model}: {url}"
+            );
+        }
+        for model in ["", ".", ".."] {
+            assert!(deployment_url(&deployments_url, &settings, model, "embeddings").is_err());
+        }
+    }
+}
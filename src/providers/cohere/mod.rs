@@ -1,39 +1,63 @@
-use std::io::{self, Write};
+use futures::future::BoxFuture;
+use futures::FutureExt as _;
+use url::Url;
 
-use crate::config::Config;
+use super::{ModelInfo, Provider, ToolCapabilities};
+use crate::config::ProviderSettings;
 
+const COHERE_CHAT_COMPLETIONS_URL: &str = "https://api.cohere.ai/compatibility/v1/chat/completions";
 const COHERE_API_KEYS_URL: &str = "https://dashboard.cohere.ai/api-keys";
+const COHERE_MODELS_URL: &str = "https://api.cohere.ai/v1/models";
 
-pub async fn login_flow(mut config: Config) -> anyhow::Result<()> {
-    println!("Opening Cohere API Keys page in your default web browser.");
-    println!("If it doesn't open automatically, please visit: {COHERE_API_KEYS_URL}");
-    if let Err(err) = webbrowser::open(COHERE_API_KEYS_URL) {
-        eprintln!("Failed to open browser: {err}");
+pub struct Cohere;
+
+impl Provider for Cohere {
+    fn id(&self) -> &'static str {
+        "cohere"
     }
 
-    print!("Please enter your Cohere API key: ");
-    io::stdout().flush()?;
-    let mut input = String::new();
-    io::stdin().read_line(&mut input)?;
-    let input = input.trim().to_string();
+    fn display_name(&self) -> &'static str {
+        "Cohere"
+    }
 
-    if input.is_empty() {
-        return Err(anyhow::anyhow!("No API key provided."));
+    fn chat_completions_url(&self, _settings: &ProviderSettings) -> anyhow::Result<Url> {
+        Ok(Url::parse(COHERE_CHAT_COMPLETIONS_URL)
+            .expect("Failed to parse Cohere chat completions URL"))
     }
 
-    config.cohere_key = Some(input);
-    if config.llm_provider.is_none() {
-        println!("Cohere is now your default LLM provider.");
-        config.llm_provider = Some(crate::config::LLMProvider::Cohere);
+    /// Cohere's compatibility API doesn't support `stream_options`.
+    fn supports_stream_usage(&self) -> bool {
+        false
     }
-    config.save()?;
 
-    println!("Your Cohere API key has been saved to the config file at:");
-    println!(
-        "{}",
-        Config::filepath()
-            .expect("Failed to get config file path")
-            .to_string_lossy()
-    );
-    Ok(())
+    /// Cohere's compatibility API can't force a specific tool and has no
+    /// `parallel_tool_calls` parameter.
+    fn tool_capabilities(&self) -> ToolCapabilities {
+        ToolCapabilities {
+            parallel_tool_calls: false,
+            tool_choice_function: false,
+            ..Default::default()
+        }
+    }
+
+    fn acquire_key(&self) -> BoxFuture<'_, anyhow::Result<String>> {
+        super::paste_key_flow(self.display_name(), COHERE_API_KEYS_URL, &[]).boxed()
+    }
+
+    /// Check the API key by listing the available models.
+    fn verify_key<'a>(
+        &'a self,
+        key: &'a str,
+        _settings: &'a ProviderSettings,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        super::verify_key_with_get(COHERE_MODELS_URL, key).boxed()
+    }
+
+    fn list_models<'a>(
+        &'a self,
+        key: &'a str,
+        _settings: &'a ProviderSettings,
+    ) -> BoxFuture<'a, anyhow::Result<Vec<ModelInfo>>> {
+        super::fetch_models(COHERE_MODELS_URL, key).boxed()
+    }
 }
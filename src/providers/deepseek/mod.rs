@@ -0,0 +1,69 @@
+use anyhow::anyhow;
+use futures::future::BoxFuture;
+use futures::FutureExt as _;
+use url::Url;
+
+use super::{ModelInfo, Provider, ToolCapabilities, Upstream};
+use crate::config::{LLMProviderDetails, ProviderSettings};
+
+const DEEPSEEK_CHAT_COMPLETIONS_URL: &str = "https://api.deepseek.com/chat/completions";
+const DEEPSEEK_API_KEYS_URL: &str = "https://platform.deepseek.com/api_keys";
+const DEEPSEEK_MODELS_URL: &str = "https://api.deepseek.com/models";
+
+pub struct DeepSeek;
+
+impl Provider for DeepSeek {
+    fn id(&self) -> &'static str {
+        "deepseek"
+    }
+
+    fn display_name(&self) -> &'static str {
+        "DeepSeek"
+    }
+
+    fn chat_completions_url(&self, _settings: &ProviderSettings) -> anyhow::Result<Url> {
+        Ok(Url::parse(DEEPSEEK_CHAT_COMPLETIONS_URL)
+            .expect("Failed to parse DeepSeek chat completions URL"))
+    }
+
+    /// DeepSeek has no `parallel_tool_calls` parameter.
+    fn tool_capabilities(&self) -> ToolCapabilities {
+        ToolCapabilities {
+            parallel_tool_calls: false,
+            ..Default::default()
+        }
+    }
+
+    fn acquire_key(&self) -> BoxFuture<'_, anyhow::Result<String>> {
+        super::paste_key_flow(self.display_name(), DEEPSEEK_API_KEYS_URL, &[]).boxed()
+    }
+
+    /// Check the API key by listing the available models.
+    fn verify_key<'a>(
+        &'a self,
+        key: &'a str,
+        _settings: &'a ProviderSettings,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        super::verify_key_with_get(DEEPSEEK_MODELS_URL, key).boxed()
+    }
+
+    fn list_models<'a>(
+        &'a self,
+        key: &'a str,
+        _settings: &'a ProviderSettings,
+    ) -> BoxFuture<'a, anyhow::Result<Vec<ModelInfo>>> {
+        super::fetch_models(DEEPSEEK_MODELS_URL, key).boxed()
+    }
+
+    /// DeepSeek doesn't offer embedding models.
+    fn embeddings_upstream(
+        &self,
+        _details: &LLMProviderDetails,
+        _model: &str,
+    ) -> anyhow::Result<Upstream> {
+        Err(anyhow!(
+            "{} does not offer embedding models",
+            self.display_name()
+        ))
+    }
+}
@@ -1,43 +1,65 @@
-use std::io::{self, Write};
+use futures::future::BoxFuture;
+use futures::FutureExt as _;
+use url::Url;
 
-use crate::config::Config;
+use super::{ModelInfo, Provider, ToolCapabilities};
+use crate::config::ProviderSettings;
 
+const GEMINI_CHAT_COMPLETIONS_URL: &str =
+    "https://generativelanguage.googleapis.com/v1beta/openai/chat/completions";
 const AISTUDIO_API_KEYS_URL: &str = "https://aistudio.google.com/app/apikey";
+const GEMINI_MODELS_URL: &str = "https://generativelanguage.googleapis.com/v1beta/openai/models";
 
-pub async fn login_flow(mut config: Config) -> anyhow::Result<()> {
-    println!("Google AI Studio should open in your default web browser.");
-    println!("If it doesn't, please visit: {AISTUDIO_API_KEYS_URL}");
-    println!("You may need to create a Google Cloud project first.");
-    println!("You can do so at: https://console.cloud.google.com");
+pub struct Gemini;
 
-    if let Err(err) = webbrowser::open(AISTUDIO_API_KEYS_URL) {
-        eprintln!("Failed to open browser: {err}");
+impl Provider for Gemini {
+    fn id(&self) -> &'static str {
+        "google-gemini"
     }
 
-    print!("Please enter your Gemini API key: ");
-    io::stdout().flush()?;
-    let mut input = String::new();
-    io::stdin().read_line(&mut input)?;
-    let input = input.trim().to_string();
+    fn display_name(&self) -> &'static str {
+        "Google Gemini"
+    }
+
+    fn chat_completions_url(&self, _settings: &ProviderSettings) -> anyhow::Result<Url> {
+        Ok(Url::parse(GEMINI_CHAT_COMPLETIONS_URL)
+            .expect("Failed to parse Gemini chat completions URL"))
+    }
+
+    /// Gemini's compatibility API has no `parallel_tool_calls` parameter.
+    fn tool_capabilities(&self) -> ToolCapabilities {
+        ToolCapabilities {
+            parallel_tool_calls: false,
+            ..Default::default()
+        }
+    }
+
+    fn acquire_key(&self) -> BoxFuture<'_, anyhow::Result<String>> {
+        super::paste_key_flow(
+            self.display_name(),
+            AISTUDIO_API_KEYS_URL,
+            &[
+                "You may need to create a Google Cloud project first.",
+                "You can do so at: https://console.cloud.google.com",
+            ],
+        )
+        .boxed()
+    }
 
-    if input.is_empty() {
-        return Err(anyhow::anyhow!("No API key provided."));
+    /// Check the API key by listing the available models.
+    fn verify_key<'a>(
+        &'a self,
+        key: &'a str,
+        _settings: &'a ProviderSettings,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        super::verify_key_with_get(GEMINI_MODELS_URL, key).boxed()
     }
 
-    // Store the key in the config and save it
-    config.google_gemini_key = Some(input);
-    if config.llm_provider.is_none() {
-        println!("Google Gemini is now your default LLM provider.");
-        config.llm_provider = Some(crate::config::LLMProvider::GoogleGemini);
+    fn list_models<'a>(
+        &'a self,
+        key: &'a str,
+        _settings: &'a ProviderSettings,
+    ) -> BoxFuture<'a, anyhow::Result<Vec<ModelInfo>>> {
+        super::fetch_models(GEMINI_MODELS_URL, key).boxed()
     }
-    config.save()?;
-
-    println!("Your Gemini API key has been saved to the config file at:");
-    println!(
-        "{}",
-        Config::filepath()
-            .expect("Failed to get config file path")
-            .to_string_lossy()
-    );
-    Ok(())
 }
@@ -1,41 +1,61 @@
-use std::io::{self, Write};
+use anyhow::anyhow;
+use futures::future::BoxFuture;
+use futures::FutureExt as _;
+use url::Url;
 
-use crate::config::Config;
+use super::{ModelInfo, Provider, Upstream};
+use crate::config::{LLMProviderDetails, ProviderSettings};
 
+const GROQ_CHAT_COMPLETIONS_URL: &str = "https://api.groq.com/openai/v1/chat/completions";
 const GROQ_API_KEYS_URL: &str = "https://console.groq.com/keys";
+const GROQ_MODELS_URL: &str = "https://api.groq.com/openai/v1/models";
 
-pub async fn login_flow(mut config: Config) -> anyhow::Result<()> {
-    println!("The Groq console should open in your default web browser.");
-    println!("If it doesn't, please visit: {GROQ_API_KEYS_URL}");
+pub struct Groq;
 
-    if let Err(err) = webbrowser::open(GROQ_API_KEYS_URL) {
-        eprintln!("Failed to open browser: {err}");
+impl Provider for Groq {
+    fn id(&self) -> &'static str {
+        "groq"
     }
 
-    print!("Please enter your Groq API key: ");
-    io::stdout().flush()?;
-    let mut input = String::new();
-    io::stdin().read_line(&mut input)?;
-    let input = input.trim().to_string();
+    fn display_name(&self) -> &'static str {
+        "Groq"
+    }
+
+    fn chat_completions_url(&self, _settings: &ProviderSettings) -> anyhow::Result<Url> {
+        Ok(Url::parse(GROQ_CHAT_COMPLETIONS_URL)
+            .expect("Failed to parse Groq chat completions URL"))
+    }
+
+    fn acquire_key(&self) -> BoxFuture<'_, anyhow::Result<String>> {
+        super::paste_key_flow(self.display_name(), GROQ_API_KEYS_URL, &[]).boxed()
+    }
+
+    /// Check the API key by listing the available models.
+    fn verify_key<'a>(
+        &'a self,
+        key: &'a str,
+        _settings: &'a ProviderSettings,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        super::verify_key_with_get(GROQ_MODELS_URL, key).boxed()
+    }
 
-    if input.is_empty() {
-        return Err(anyhow::anyhow!("No API key provided."));
+    fn list_models<'a>(
+        &'a self,
+        key: &'a str,
+        _settings: &'a ProviderSettings,
+    ) -> BoxFuture<'a, anyhow::Result<Vec<ModelInfo>>> {
+        super::fetch_models(GROQ_MODELS_URL, key).boxed()
     }
 
-    // Store the key in the config and save it
-    config.groq_key = Some(input);
-    if config.llm_provider.is_none() {
-        println!("Groq is now your default LLM provider.");
-        config.llm_provider = Some(crate::config::LLMProvider::Groq);
+    /// Groq doesn't offer embedding models.
+    fn embeddings_upstream(
+        &self,
+        _details: &LLMProviderDetails,
+        _model: &str,
+    ) -> anyhow::Result<Upstream> {
+        Err(anyhow!(
+            "{} does not offer embedding models",
+            self.display_name()
+        ))
     }
-    config.save()?;
-
-    println!("Your Groq API key has been saved to the config file at:");
-    println!(
-        "{}",
-        Config::filepath()
-            .expect("Failed to get config file path")
-            .to_string_lossy()
-    );
-    Ok(())
 }
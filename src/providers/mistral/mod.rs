@@ -0,0 +1,48 @@
+use futures::future::BoxFuture;
+use futures::FutureExt as _;
+use url::Url;
+
+use super::{ModelInfo, Provider};
+use crate::config::ProviderSettings;
+
+const MISTRAL_CHAT_COMPLETIONS_URL: &str = "https://api.mistral.ai/v1/chat/completions";
+const MISTRAL_API_KEYS_URL: &str = "https://console.mistral.ai/api-keys";
+const MISTRAL_MODELS_URL: &str = "https://api.mistral.ai/v1/models";
+
+pub struct Mistral;
+
+impl Provider for Mistral {
+    fn id(&self) -> &'static str {
+        "mistral"
+    }
+
+    fn display_name(&self) -> &'static str {
+        "Mistral"
+    }
+
+    fn chat_completions_url(&self, _settings: &ProviderSettings) -> anyhow::Result<Url> {
+        Ok(Url::parse(MISTRAL_CHAT_COMPLETIONS_URL)
+            .expect("Failed to parse Mistral chat completions URL"))
+    }
+
+    fn acquire_key(&self) -> BoxFuture<'_, anyhow::Result<String>> {
+        super::paste_key_flow(self.display_name(), MISTRAL_API_KEYS_URL, &[]).boxed()
+    }
+
+    /// Check the API key by listing the available models.
+    fn verify_key<'a>(
+        &'a self,
+        key: &'a str,
+        _settings: &'a ProviderSettings,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        super::verify_key_with_get(MISTRAL_MODELS_URL, key).boxed()
+    }
+
+    fn list_models<'a>(
+        &'a self,
+        key: &'a str,
+        _settings: &'a ProviderSettings,
+    ) -> BoxFuture<'a, anyhow::Result<Vec<ModelInfo>>> {
+        super::fetch_models(MISTRAL_MODELS_URL, key).boxed()
+    }
+}
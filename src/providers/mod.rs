@@ -1,4 +1,339 @@
+//! LLM providers.
+//!
+//! Each provider is a self-contained module implementing [`Provider`] and is listed in
+//! [`PROVIDERS`]. The registry drives the `login` command, how keys are stored in the config
+//! and how requests are routed.
+
+use anyhow::anyhow;
+use futures::future::BoxFuture;
+use once_cell::sync::Lazy;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use url::Url;
+
+use crate::config::{Config, LLMProviderDetails, ProviderSettings};
+
+pub mod azure;
 pub mod cohere;
+pub mod deepseek;
 pub mod gemini;
 pub mod groq;
+pub mod mistral;
+pub mod openai;
 pub mod openrouter;
+pub mod together;
+
+/// Client for requests forwarded to providers, sharing connections between requests.
+static HTTP_CLIENT: Lazy<reqwest::Client> = Lazy::new(reqwest::Client::new);
+
+/// All supported providers.
+pub static PROVIDERS: &[&dyn Provider] = &[
+    &openrouter::OpenRouter,
+    &openai::OpenAI,
+    &azure::Azure,
+    &groq::Groq,
+    &gemini::Gemini,
+    &cohere::Cohere,
+    &mistral::Mistral,
+    &deepseek::DeepSeek,
+    &together::Together,
+];
+
+pub trait Provider: Sync {
+    /// Identifier used in the config, on the command line and as model prefix.
+    fn id(&self) -> &'static str;
+
+    /// Name shown to users.
+    fn display_name(&self) -> &'static str;
+
+    /// Check the provider-specific settings in `[providers.<id>]`, i.e. all but the rate
+    /// limits. Returns the names of the settings the provider doesn't know. By default, a
+    /// provider has no specific settings.
+    fn check_settings(&self, settings: &ProviderSettings) -> anyhow::Result<Vec<String>> {
+        Ok(settings.options.keys().cloned().collect())
+    }
+
+    /// The provider's OpenAI-compatible chat completions endpoint.
+    fn chat_completions_url(&self, settings: &ProviderSettings) -> anyhow::Result<Url>;
+
+    /// Obtain an API key from the user, e.g. by opening the provider's key page.
+    fn acquire_key(&self) -> BoxFuture<'_, anyhow::Result<String>>;
+
+    /// Check that an API key is accepted by the provider.
+    fn verify_key<'a>(
+        &'a self,
+        key: &'a str,
+        settings: &'a ProviderSettings,
+    ) -> BoxFuture<'a, anyhow::Result<()>>;
+
+    /// List the models available with the given API key.
+    fn list_models<'a>(
+        &'a self,
+        key: &'a str,
+        settings: &'a ProviderSettings,
+    ) -> BoxFuture<'a, anyhow::Result<Vec<ModelInfo>>>;
+
+    /// Whether the provider accepts `stream_options.include_usage` to report token usage at
+    /// the end of streamed responses.
+    fn supports_stream_usage(&self) -> bool {
+        true
+    }
+
+    /// The request parameter limiting the number of generated tokens. OpenAI's reasoning
+    /// models (the o-series and later) reject the older `max_tokens`.
+    fn max_tokens_param(&self) -> &'static str {
+        "max_tokens"
+    }
+
+    /// Which parts of OpenAI-style tool calling the provider supports.
+    fn tool_capabilities(&self) -> ToolCapabilities {
+        ToolCapabilities::default()
+    }
+
+    /// Where and how to forward a chat completion request for the model.
+    fn chat_upstream(&self, details: &LLMProviderDetails, model: &str) -> anyhow::Result<Upstream> {
+        Ok(Upstream {
+            url: details.api_chat_completions_endpoint.clone(),
+            model: model.to_owned(),
+            headers: vec![("Authorization", format!("Bearer {}", details.api_key))],
+        })
+    }
+
+    /// Where and how to forward an embeddings request for the model. Fails if the provider
+    /// doesn't offer embeddings. By default, requests are sent to the `embeddings` endpoint
+    /// next to the chat completions endpoint.
+    fn embeddings_upstream(
+        &self,
+        details: &LLMProviderDetails,
+        model: &str,
+    ) -> anyhow::Result<Upstream> {
+        let mut upstream = self.chat_upstream(details, model)?;
+        let query = upstream.url.query().map(str::to_owned);
+        upstream.url = upstream
+            .url
+            .join("../embeddings")
+            .expect("Failed to build embeddings URL");
+        upstream.url.set_query(query.as_deref());
+        Ok(upstream)
+    }
+}
+
+/// Where and how a request is forwarded to a provider.
+pub struct Upstream {
+    pub url: Url,
+    /// The model as named by the provider.
+    pub model: String,
+    /// Headers authenticating the request, and any other headers the provider expects.
+    pub headers: Vec<(&'static str, String)>,
+}
+
+impl Upstream {
+    /// Send a JSON request body to the provider, with the model replaced by the provider's
+    /// name for it.
+    pub async fn post(&self, body: &Value) -> reqwest::Result<reqwest::Response> {
+        let mut body = body.clone();
+        body["model"] = Value::String(self.model.clone());
+        let mut request = HTTP_CLIENT.post(self.url.clone()).json(&body);
+        for (name, value) in &self.headers {
+            request = request.header(*name, value);
+        }
+        request.send().await
+    }
+}
+
+/// Support for OpenAI-style tool calling. Requests using unsupported features are rejected
+/// by the chat proxy with a clear error, or rewritten where that doesn't change their meaning.
+#[derive(Clone, Copy)]
+pub struct ToolCapabilities {
+    /// Whether the provider supports tools at all.
+    pub tools: bool,
+    /// Whether the provider accepts the `parallel_tool_calls` parameter.
+    pub parallel_tool_calls: bool,
+    /// Whether `tool_choice` can be `"required"`.
+    pub tool_choice_required: bool,
+    /// Whether `tool_choice` can name a specific function.
+    pub tool_choice_function: bool,
+}
+
+impl Default for ToolCapabilities {
+    fn default() -> Self {
+        Self {
+            tools: true,
+            parallel_tool_calls: true,
+            tool_choice_required: true,
+            tool_choice_function: true,
+        }
+    }
+}
+
+/// A model offered by a provider.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ModelInfo {
+    pub id: String,
+    /// Maximum number of tokens in the context window, if known.
+    pub context_window: Option<u64>,
+    /// Price in USD per prompt token, if known.
+    pub prompt_price: Option<f64>,
+    /// Price in USD per completion token, if known.
+    pub completion_price: Option<f64>,
+}
+
+/// Look up a provider by its identifier.
+pub fn get(id: &str) -> Option<&'static dyn Provider> {
+    PROVIDERS
+        .iter()
+        .copied()
+        .find(|provider| provider.id() == id)
+}
+
+/// Identifiers of all providers.
+pub fn ids() -> Vec<&'static str> {
+    PROVIDERS.iter().map(|provider| provider.id()).collect()
+}
+
+/// Environment variable from which the provider's API key is read at run time, if set.
+pub fn key_env_var(provider: &dyn Provider) -> String {
+    format!(
+        "MINION_{}_KEY",
+        provider.id().to_uppercase().replace('-', "_")
+    )
+}
+
+/// Obtain an API key from the user and store it in the selected profile.
+pub async fn login_flow(provider: &dyn Provider, config: Config) -> anyhow::Result<()> {
+    let key = provider.acquire_key().await?;
+    save_key(config, provider, key)
+}
+
+/// Store an API key (or secret reference) for the provider in the selected profile.
+/// The provider becomes the profile's default if it has none yet.
+pub fn save_key(mut config: Config, provider: &dyn Provider, key: String) -> anyhow::Result<()> {
+    let profile = config.profile_mut();
+    profile.keys.insert(provider.id().to_owned(), key);
+    if profile.llm_provider.is_none() {
+        profile.llm_provider = Some(provider.id().to_owned());
+        println!(
+            "{} is now the default LLM provider of {}.",
+            provider.display_name(),
+            config.profile_description()
+        );
+    }
+    config.save()?;
+
+    println!(
+        "Your {} API key has been saved to {} in the config file at:",
+        provider.display_name(),
+        config.profile_description()
+    );
+    println!(
+        "{}",
+        Config::filepath()
+            .expect("Failed to get config file path")
+            .to_string_lossy()
+    );
+    Ok(())
+}
+
+/// Check provider-specific settings against the provider's settings type `T`, see
+/// [`Provider::check_settings`]. Settings that don't survive a round trip through `T` are
+/// unknown.
+fn check_settings<T: DeserializeOwned + Serialize>(
+    settings: &ProviderSettings,
+) -> anyhow::Result<Vec<String>> {
+    let parsed: T = settings.parse_options()?;
+    let known = toml::Table::try_from(&parsed)?;
+    Ok(settings
+        .options
+        .keys()
+        .filter(|key| !known.contains_key(*key))
+        .cloned()
+        .collect())
+}
+
+/// Ask the user to paste an API key after pointing them to the provider's key page.
+async fn paste_key_flow(
+    display_name: &str,
+    keys_url: &str,
+    hints: &[&str],
+) -> anyhow::Result<String> {
+    if crate::util::open_browser(keys_url) {
+        println!("The {display_name} API keys page should open in your default web browser.");
+        println!("If it doesn't, please visit: {keys_url}");
+    }
+    for hint in hints {
+        println!("{hint}");
+    }
+
+    crate::util::read_api_key(display_name)
+}
+
+/// Check an API key by making an authenticated GET request to a cheap endpoint.
+async fn verify_key_with_get(url: &str, key: &str) -> anyhow::Result<()> {
+    let response = reqwest::Client::new()
+        .get(url)
+        .bearer_auth(key)
+        .send()
+        .await?;
+    match response.status() {
+        status if status.is_success() => Ok(()),
+        status @ (reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN) => {
+            Err(anyhow!("Key rejected ({status})"))
+        }
+        status => Err(anyhow!("Unexpected response ({status})")),
+    }
+}
+
+/// Fetch a model listing in the OpenAI format (`{"data": [{"id": ...}]}`) or a similar
+/// format (`{"models": [{"name": ...}]}` or a bare array), picking up context window and
+/// pricing metadata where the provider includes it.
+async fn fetch_models(url: &str, key: &str) -> anyhow::Result<Vec<ModelInfo>> {
+    let response = get_json(url, key).await?;
+    Ok(model_entries(&response)?
+        .iter()
+        .filter_map(model_info)
+        .collect())
+}
+
+/// Make an authenticated GET request and parse the response as JSON.
+async fn get_json(url: &str, key: &str) -> anyhow::Result<Value> {
+    Ok(reqwest::Client::new()
+        .get(url)
+        .bearer_auth(key)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?)
+}
+
+/// The entries of a model listing.
+fn model_entries(response: &Value) -> anyhow::Result<&Vec<Value>> {
+    response
+        .as_array()
+        .or_else(|| response["data"].as_array())
+        .or_else(|| response["models"].as_array())
+        .ok_or_else(|| anyhow!("Unexpected model listing format"))
+}
+
+/// Parse an entry of a model listing.
+fn model_info(entry: &Value) -> Option<ModelInfo> {
+    let id = entry["id"].as_str().or_else(|| entry["name"].as_str())?;
+    Some(ModelInfo {
+        // Gemini prefixes model IDs with "models/".
+        id: id.trim_start_matches("models/").to_owned(),
+        context_window: entry["context_length"]
+            .as_u64()
+            .or_else(|| entry["context_window"].as_u64())
+            .or_else(|| entry["max_context_length"].as_u64()),
+        prompt_price: number(&entry["pricing"]["prompt"]),
+        completion_price: number(&entry["pricing"]["completion"]),
+    })
+}
+
+/// A number that may be encoded as a JSON number or a string.
+fn number(value: &Value) -> Option<f64> {
+    value
+        .as_f64()
+        .or_else(|| value.as_str().and_then(|s| s.parse().ok()))
+}
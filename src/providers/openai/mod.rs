@@ -0,0 +1,92 @@
+use futures::future::BoxFuture;
+use futures::FutureExt as _;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use super::{ModelInfo, Provider, Upstream};
+use crate::config::{LLMProviderDetails, ProviderSettings};
+
+const OPENAI_CHAT_COMPLETIONS_URL: &str = "https://api.openai.com/v1/chat/completions";
+const OPENAI_API_KEYS_URL: &str = "https://platform.openai.com/api-keys";
+const OPENAI_MODELS_URL: &str = "https://api.openai.com/v1/models";
+
+pub struct OpenAI;
+
+/// Settings in `[providers.openai]`.
+#[derive(Default, Serialize, Deserialize)]
+struct Settings {
+    /// Organization ID, sent as the `OpenAI-Organization` header.
+    organization: Option<String>,
+    /// Project ID, sent as the `OpenAI-Project` header.
+    project: Option<String>,
+}
+
+impl Provider for OpenAI {
+    fn id(&self) -> &'static str {
+        "openai"
+    }
+
+    fn display_name(&self) -> &'static str {
+        "OpenAI"
+    }
+
+    fn chat_completions_url(&self, _settings: &ProviderSettings) -> anyhow::Result<Url> {
+        Ok(Url::parse(OPENAI_CHAT_COMPLETIONS_URL)
+            .expect("Failed to parse OpenAI chat completions URL"))
+    }
+
+    fn check_settings(&self, settings: &ProviderSettings) -> anyhow::Result<Vec<String>> {
+        super::check_settings::<Settings>(settings)
+    }
+
+    /// `max_completion_tokens` is accepted by all models, `max_tokens` not by reasoning models.
+    fn max_tokens_param(&self) -> &'static str {
+        "max_completion_tokens"
+    }
+
+    fn acquire_key(&self) -> BoxFuture<'_, anyhow::Result<String>> {
+        super::paste_key_flow(
+            self.display_name(),
+            OPENAI_API_KEYS_URL,
+            &[
+                "To bill a specific organization or project, set `providers.openai.organization`",
+                "or `providers.openai.project` with `minion config set` afterwards.",
+            ],
+        )
+        .boxed()
+    }
+
+    /// Check the API key by listing the available models.
+    fn verify_key<'a>(
+        &'a self,
+        key: &'a str,
+        _settings: &'a ProviderSettings,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        super::verify_key_with_get(OPENAI_MODELS_URL, key).boxed()
+    }
+
+    fn list_models<'a>(
+        &'a self,
+        key: &'a str,
+        _settings: &'a ProviderSettings,
+    ) -> BoxFuture<'a, anyhow::Result<Vec<ModelInfo>>> {
+        super::fetch_models(OPENAI_MODELS_URL, key).boxed()
+    }
+
+    /// Send the configured organization and project along with each request.
+    fn chat_upstream(&self, details: &LLMProviderDetails, model: &str) -> anyhow::Result<Upstream> {
+        let settings: Settings = details.settings.parse_options()?;
+        let mut headers = vec![("Authorization", format!("Bearer {}", details.api_key))];
+        if let Some(organization) = settings.organization {
+            headers.push(("OpenAI-Organization", organization));
+        }
+        if let Some(project) = settings.project {
+            headers.push(("OpenAI-Project", project));
+        }
+        Ok(Upstream {
+            url: details.api_chat_completions_endpoint.clone(),
+            model: model.to_owned(),
+            headers,
+        })
+    }
+}
@@ -1,6 +1,9 @@
 use actix_web::{get, http::header::LOCATION, web, HttpResponse};
 use actix_web::{middleware, App, HttpServer};
+use anyhow::anyhow;
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use futures::future::BoxFuture;
+use futures::FutureExt as _;
 use once_cell::sync::Lazy;
 use rand::RngCore as _;
 use serde::{Deserialize, Serialize};
@@ -9,7 +12,11 @@ use std::time::Duration;
 use tokio::sync::{oneshot, Mutex};
 use url::Url;
 
-use crate::config::Config;
+use super::{ModelInfo, Provider};
+use crate::config::ProviderSettings;
+
+/// OpenRouter’s chat completions endpoint.
+const CHAT_COMPLETIONS_URL: &str = "https://openrouter.ai/api/v1/chat/completions";
 
 /// OpenRouter’s authorization URL.
 static OAUTH_AUTHORIZE_URL: Lazy<Url> =
@@ -19,40 +26,85 @@ static OAUTH_AUTHORIZE_URL: Lazy<Url> =
 static AUTH_KEY_URL: Lazy<Url> =
     Lazy::new(|| Url::parse("https://openrouter.ai/api/v1/auth/keys").unwrap());
 
+/// OpenRouter’s endpoint for information about the current API key.
+const KEY_INFO_URL: &str = "https://openrouter.ai/api/v1/auth/key";
+
+/// OpenRouter’s page for managing API keys, used if the browser flow doesn't complete.
+const API_KEYS_URL: &str = "https://openrouter.ai/settings/keys";
+
+/// OpenRouter’s model listing, including context lengths and pricing.
+const MODELS_URL: &str = "https://openrouter.ai/api/v1/models";
+
+/// How long to wait for the browser flow to complete.
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(300);
+
+pub struct OpenRouter;
+
+impl Provider for OpenRouter {
+    fn id(&self) -> &'static str {
+        "openrouter"
+    }
+
+    fn display_name(&self) -> &'static str {
+        "OpenRouter"
+    }
+
+    fn chat_completions_url(&self, _settings: &ProviderSettings) -> anyhow::Result<Url> {
+        Ok(Url::parse(CHAT_COMPLETIONS_URL)
+            .expect("Failed to parse OpenRouter chat completions URL"))
+    }
+
+    fn acquire_key(&self) -> BoxFuture<'_, anyhow::Result<String>> {
+        login_flow().boxed()
+    }
+
+    /// Check the API key by requesting information about it.
+    fn verify_key<'a>(
+        &'a self,
+        key: &'a str,
+        _settings: &'a ProviderSettings,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        super::verify_key_with_get(KEY_INFO_URL, key).boxed()
+    }
+
+    fn list_models<'a>(
+        &'a self,
+        key: &'a str,
+        _settings: &'a ProviderSettings,
+    ) -> BoxFuture<'a, anyhow::Result<Vec<ModelInfo>>> {
+        super::fetch_models(MODELS_URL, key).boxed()
+    }
+}
+
 /// Context for the auth flow
 #[derive(Clone)]
 pub struct Context {
-    pub config: Config,
     pub code_verifier: String,
+    /// Random path segment of the callback URL, which ties the callback to this flow.
+    pub state: String,
     pub web_base_url: Url,
 }
 
-/// Start a temporary web server for the OpenRouter auth flow.
-pub async fn login_flow(config: Config) -> anyhow::Result<()> {
-    let host = "127.0.0.1";
-    let port = 3000;
-    let bind_addr = format!("{host}:{port}");
-    let web_base_url = Url::parse(&format!("http://{bind_addr}")).unwrap();
-
-    println!("The login page should open in your default web browser.");
-    println!(
-        "If it doesn't, please visit: {}",
-        web_base_url.join("/auth/openrouter").unwrap()
-    );
+/// Start a temporary web server for the OpenRouter auth flow and return the obtained key.
+/// Falls back to asking for an API key if the flow doesn't complete in time.
+async fn login_flow() -> anyhow::Result<String> {
+    let listener = crate::util::listen_to_free_port("127.0.0.1");
+    let port = listener.local_addr()?.port();
+    let web_base_url = Url::parse(&format!("http://127.0.0.1:{port}")).unwrap();
 
     let context = Context {
-        config,
         code_verifier: code_verifier(),
+        state: crate::context::random_key(),
         web_base_url: web_base_url.clone(),
     };
 
-    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
-    let shutdown_tx = web::Data::new(Mutex::new(Some(shutdown_tx)));
+    let (key_tx, key_rx) = oneshot::channel::<String>();
+    let key_tx = web::Data::new(Mutex::new(Some(key_tx)));
 
     let server = HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(context.clone()))
-            .app_data(shutdown_tx.clone())
+            .app_data(key_tx.clone())
             .service(openrouter_connect)
             .service(openrouter_auth_code)
             .wrap(middleware::NormalizePath::new(
@@ -60,26 +112,43 @@ pub async fn login_flow(config: Config) -> anyhow::Result<()> {
             ))
             .wrap(middleware::Logger::default())
     })
-    .bind(bind_addr)?
-    .shutdown_timeout(0)
+    .listen(listener)?
+    // Leaves time to deliver the page of the callback that completed the flow.
+    .shutdown_timeout(1)
     .run();
+    let server_handle = server.handle();
 
     // Spawn a task to open the login flow URL in the browser after a brief delay.
     // This delay gives the server time to start.
     let login_url = web_base_url.join("/auth/openrouter").unwrap();
     tokio::spawn(async move {
         tokio::time::sleep(Duration::from_millis(500)).await;
-        if let Err(err) = webbrowser::open(login_url.as_str()) {
-            eprintln!("Failed to open browser: {err}");
+        if crate::util::open_browser(login_url.as_str()) {
+            println!("The login page should open in your default web browser.");
+            println!("If it doesn't, please visit: {login_url}");
         }
     });
 
-    tokio::select! {
-        _ = shutdown_rx => {}
-        res = server => { res? }
-    }
+    let completed = tokio::time::timeout(LOGIN_TIMEOUT, async {
+        tokio::select! {
+            key = key_rx => Ok(key?),
+            res = server => res
+                .map_err(anyhow::Error::from)
+                .and_then(|()| Err(anyhow!("Login server stopped unexpectedly"))),
+        }
+    })
+    .await;
+    server_handle.stop(true).await;
 
-    Ok(())
+    match completed {
+        Ok(res) => res,
+        Err(_) => {
+            println!();
+            println!("Timed out waiting for the browser login to complete.");
+            println!("You can instead create an API key at {API_KEYS_URL} and paste it here.");
+            crate::util::read_api_key("OpenRouter")
+        }
+    }
 }
 
 /// This endpoint initiates the OpenRouter login flow by redirecting the user’s browser.
@@ -91,7 +160,7 @@ async fn openrouter_connect(context: web::Data<Context>) -> HttpResponse {
     let mut location = OAUTH_AUTHORIZE_URL.clone();
     let callback_url = context
         .web_base_url
-        .join("/auth/openrouter/auth-code")
+        .join(&format!("/auth/openrouter/callback/{}", context.state))
         .unwrap();
     location
         .query_pairs_mut()
@@ -107,56 +176,105 @@ async fn openrouter_connect(context: web::Data<Context>) -> HttpResponse {
 /// Query parameters for the auth-code callback.
 #[derive(Deserialize)]
 struct AuthCodeQuery {
-    code: String,
+    code: Option<String>,
 }
 
 /// This endpoint is the callback for the OpenRouter auth flow. It receives the
 /// authorization code, uses the stored PKCE verifier to request an auth key,
-/// saves that key in the config file, and then notifies the user.
-#[get("/auth/openrouter/auth-code")]
+/// hands that key to the login flow, and then notifies the user.
+#[get("/auth/openrouter/callback/{state}")]
 async fn openrouter_auth_code(
     context: web::Data<Context>,
-    shutdown_tx: web::Data<Mutex<Option<oneshot::Sender<()>>>>,
+    key_tx: web::Data<Mutex<Option<oneshot::Sender<String>>>>,
+    state: web::Path<String>,
     query: web::Query<AuthCodeQuery>,
 ) -> HttpResponse {
+    if *state != context.state {
+        return html_page(
+            HttpResponse::BadRequest(),
+            "Authentication failed",
+            "The login request did not originate from this login session. Please start the login again.",
+        );
+    }
+    let Some(code) = &query.code else {
+        return html_page(
+            HttpResponse::BadRequest(),
+            "Authentication failed",
+            "OpenRouter did not provide an authorization code. Please try again.",
+        );
+    };
+
     let code_verifier = &context.code_verifier;
-    let key = match auth_key(&query.code, code_verifier).await {
+    let key = match auth_key(code, code_verifier).await {
         Ok(key) => key,
         Err(err) => {
-            return HttpResponse::InternalServerError()
-                .body(format!("Failed to get auth key: {err}"));
+            return html_page(
+                HttpResponse::InternalServerError(),
+                "Authentication failed",
+                &format!("Failed to get auth key: {err}"),
+            );
         }
     };
 
-    // Update the configuration with the obtained key and save it.
-    let mut config = context.config.clone();
-    config.openrouter_key = Some(key);
-    if config.llm_provider.is_none() {
-        println!("OpenRouter is now your default LLM provider.");
-        config.llm_provider = Some(crate::config::LLMProvider::OpenRouter);
-    }
-    if let Err(err) = config.save() {
-        return HttpResponse::InternalServerError().body(format!("Failed to save config: {err}"));
+    let Some(tx) = key_tx.lock().await.take() else {
+        return html_page(
+            HttpResponse::Conflict(),
+            "Already authenticated",
+            "This login session has already completed. You can close this window.",
+        );
+    };
+    // The login flow stops waiting for the key when it times out.
+    if tx.send(key).is_err() {
+        return html_page(
+            HttpResponse::Gone(),
+            "Login session expired",
+            "This login session timed out. Please start the login again.",
+        );
     }
-
     println!();
     println!("Authentication successful.");
-    println!("Your OpenRouter API key has been saved to the config file at:");
-    println!(
-        "{}",
-        Config::filepath()
-            .expect("Failed to get config file path")
-            .to_string_lossy()
-    );
-
-    let tx = shutdown_tx
-        .lock()
-        .await
-        .take()
-        .expect("Failed to acquire lock for shutdown signal");
-    tx.send(()).expect("Failed to send shutdown signal");
-
-    HttpResponse::Ok().body("Authentication successful! You can close this window.")
+
+    html_page(
+        HttpResponse::Ok(),
+        "Authentication successful",
+        "You are now logged in to OpenRouter. You can close this window.",
+    )
+}
+
+/// Render a minimal HTML page with a title and a message.
+fn html_page(
+    mut response: actix_web::HttpResponseBuilder,
+    title: &str,
+    message: &str,
+) -> HttpResponse {
+    let title = html_escape(title);
+    let message = html_escape(message);
+    response
+        .content_type("text/html; charset=utf-8")
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>minion: {title}</title>
+<style>
+body {{ font-family: system-ui, sans-serif; max-width: 32rem; margin: 4rem auto; padding: 0 1rem; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+<p>{message}</p>
+</body>
+</html>
+"#
+        ))
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
 }
 
 /// Requests an auth key from OpenRouter by exchanging the authorization code
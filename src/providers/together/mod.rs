@@ -0,0 +1,65 @@
+use futures::future::BoxFuture;
+use futures::FutureExt as _;
+use url::Url;
+
+use super::{ModelInfo, Provider};
+use crate::config::ProviderSettings;
+
+const TOGETHER_CHAT_COMPLETIONS_URL: &str = "https://api.together.xyz/v1/chat/completions";
+const TOGETHER_API_KEYS_URL: &str = "https://api.together.ai/settings/api-keys";
+const TOGETHER_MODELS_URL: &str = "https://api.together.xyz/v1/models";
+
+pub struct Together;
+
+impl Provider for Together {
+    fn id(&self) -> &'static str {
+        "together"
+    }
+
+    fn display_name(&self) -> &'static str {
+        "Together AI"
+    }
+
+    fn chat_completions_url(&self, _settings: &ProviderSettings) -> anyhow::Result<Url> {
+        Ok(Url::parse(TOGETHER_CHAT_COMPLETIONS_URL)
+            .expect("Failed to parse Together AI chat completions URL"))
+    }
+
+    fn acquire_key(&self) -> BoxFuture<'_, anyhow::Result<String>> {
+        super::paste_key_flow(self.display_name(), TOGETHER_API_KEYS_URL, &[]).boxed()
+    }
+
+    /// Check the API key by listing the available models.
+    fn verify_key<'a>(
+        &'a self,
+        key: &'a str,
+        _settings: &'a ProviderSettings,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        super::verify_key_with_get(TOGETHER_MODELS_URL, key).boxed()
+    }
+
+    /// List the chat models. Together AI reports prices in USD per million tokens.
+    fn list_models<'a>(
+        &'a self,
+        key: &'a str,
+        _settings: &'a ProviderSettings,
+    ) -> BoxFuture<'a, anyhow::Result<Vec<ModelInfo>>> {
+        async move {
+            let response = super::get_json(TOGETHER_MODELS_URL, key).await?;
+            Ok(super::model_entries(&response)?
+                .iter()
+                .filter(|entry| entry["type"].as_str() == Some("chat"))
+                .filter_map(|entry| {
+                    let mut model = super::model_info(entry)?;
+                    let per_million = |price: &serde_json::Value| {
+                        super::number(price).map(|price| price / 1_000_000.0)
+                    };
+                    model.prompt_price = per_million(&entry["pricing"]["input"]);
+                    model.completion_price = per_million(&entry["pricing"]["output"]);
+                    Some(model)
+                })
+                .collect())
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,250 @@
+//! Client-side rate limiting of requests to LLM providers.
+//!
+//! Limits are configured per provider in `[providers.<id>]` and enforced by the chat proxy,
+//! which queues requests until they fit the limits.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use actix_web::http::header::HttpDate;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::config::{LLMRouterTable, ProviderSettings};
+
+/// The window over which requests and tokens per minute are counted.
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Longest time requests are held back at a provider's request, in case it asks for more.
+const MAX_BLOCK: Duration = Duration::from_secs(10 * 60);
+
+/// Rate limiters of the providers that have limits configured, by provider ID.
+#[derive(Default)]
+pub struct RateLimits {
+    limiters: HashMap<String, RateLimiter>,
+}
+
+impl RateLimits {
+    pub fn new(llm_router_table: &LLMRouterTable) -> Self {
+        let limiters = llm_router_table
+            .providers
+            .iter()
+            .filter_map(|(id, details)| {
+                RateLimiter::new(&details.settings).map(|limiter| (id.clone(), limiter))
+            })
+            .collect();
+        Self { limiters }
+    }
+
+    /// The rate limiter of the provider, if it has limits configured.
+    pub fn get(&self, provider_id: &str) -> Option<&RateLimiter> {
+        self.limiters.get(provider_id)
+    }
+}
+
+pub struct RateLimiter {
+    requests_per_minute: Option<usize>,
+    tokens_per_minute: Option<u64>,
+    concurrency: Option<Arc<Semaphore>>,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Times of the requests made within the last minute.
+    requests: VecDeque<Instant>,
+    /// Tokens used within the last minute.
+    tokens: VecDeque<(Instant, u64)>,
+    /// Time until which the provider asked us not to send requests.
+    blocked_until: Option<Instant>,
+}
+
+/// Permission to send a request. Holds a concurrency slot until dropped.
+pub struct Permit {
+    _concurrency: Option<OwnedSemaphorePermit>,
+}
+
+impl RateLimiter {
+    /// Create a rate limiter from the provider's settings. Returns `None` if no limits are set.
+    fn new(settings: &ProviderSettings) -> Option<Self> {
+        if settings.requests_per_minute.is_none()
+            && settings.tokens_per_minute.is_none()
+            && settings.max_concurrent.is_none()
+        {
+            return None;
+        }
+        Some(Self {
+            requests_per_minute: settings.requests_per_minute,
+            tokens_per_minute: settings.tokens_per_minute,
+            concurrency: settings
+                .max_concurrent
+                .map(|max| Arc::new(Semaphore::new(max.max(1)))),
+            state: Mutex::default(),
+        })
+    }
+
+    /// Wait until a request using about `tokens` tokens fits the limits.
+    /// `on_wait` is called whenever the request has to wait, with the expected wait time or
+    /// zero while waiting for a concurrency slot.
+    pub async fn acquire(&self, tokens: u64, mut on_wait: impl FnMut(Duration)) -> Permit {
+        let concurrency = match &self.concurrency {
+            Some(semaphore) => {
+                let permit = match semaphore.clone().try_acquire_owned() {
+                    Ok(permit) => permit,
+                    Err(_) => {
+                        on_wait(Duration::ZERO);
+                        semaphore
+                            .clone()
+                            .acquire_owned()
+                            .await
+                            .expect("Rate limit semaphore is never closed")
+                    }
+                };
+                Some(permit)
+            }
+            None => None,
+        };
+
+        loop {
+            let wait = {
+                let mut state = self.state.lock().expect("Rate limit lock poisoned");
+                let wait = self.wait_time(&mut state, tokens);
+                if wait.is_zero() {
+                    let now = Instant::now();
+                    state.requests.push_back(now);
+                    state.tokens.push_back((now, tokens));
+                }
+                wait
+            };
+            if wait.is_zero() {
+                break;
+            }
+            on_wait(wait);
+            tokio::time::sleep(wait).await;
+        }
+
+        Permit {
+            _concurrency: concurrency,
+        }
+    }
+
+    /// Time to wait before a request using `tokens` tokens fits the limits.
+    fn wait_time(&self, state: &mut State, tokens: u64) -> Duration {
+        let now = Instant::now();
+        let expired = |at: &Instant| now.duration_since(*at) >= WINDOW;
+        while state.requests.front().is_some_and(expired) {
+            state.requests.pop_front();
+        }
+        while state.tokens.front().is_some_and(|(at, _)| expired(at)) {
+            state.tokens.pop_front();
+        }
+        if let Some(until) = state.blocked_until {
+            if until > now {
+                return until - now;
+            }
+            state.blocked_until = None;
+        }
+
+        // Time until the oldest entry no longer counts.
+        let until_expired = |at: &Instant| WINDOW.saturating_sub(now.duration_since(*at));
+        if self
+            .requests_per_minute
+            .is_some_and(|limit| state.requests.len() >= limit)
+        {
+            return state
+                .requests
+                .front()
+                .map(until_expired)
+                .unwrap_or_default();
+        }
+        let used: u64 = state.tokens.iter().map(|(_, tokens)| tokens).sum();
+        // A request larger than the limit is let through once the window is empty.
+        if self
+            .tokens_per_minute
+            .is_some_and(|limit| !state.tokens.is_empty() && used + tokens > limit)
+        {
+            return state
+                .tokens
+                .front()
+                .map(|(at, _)| until_expired(at))
+                .unwrap_or_default();
+        }
+        Duration::ZERO
+    }
+
+    /// Count tokens that only became known after the request, e.g. completion tokens.
+    pub fn add_tokens(&self, tokens: u64) {
+        let mut state = self.state.lock().expect("Rate limit lock poisoned");
+        state.tokens.push_back((Instant::now(), tokens));
+    }
+
+    /// Hold back all requests for the given time, e.g. as requested by a `retry-after` header,
+    /// but no longer than ten minutes.
+    pub fn block_for(&self, duration: Duration) {
+        let mut state = self.state.lock().expect("Rate limit lock poisoned");
+        let Some(until) = Instant::now().checked_add(duration.min(MAX_BLOCK)) else {
+            return;
+        };
+        state.blocked_until = Some(
+            state
+                .blocked_until
+                .map_or(until, |blocked| blocked.max(until)),
+        );
+    }
+}
+
+/// Parse the value of a `retry-after` header, given either in seconds or as an HTTP date.
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<f64>() {
+        return Duration::try_from_secs_f64(seconds).ok();
+    }
+    let date: SystemTime = value.parse::<HttpDate>().ok()?.into();
+    // A date in the past means the request can be retried right away.
+    Some(
+        date.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_retry_after_seconds() {
+        assert_eq!(parse_retry_after("30"), Some(Duration::from_secs(30)));
+        assert_eq!(
+            parse_retry_after(" 1.5 "),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(parse_retry_after("-1"), None);
+        assert_eq!(parse_retry_after("1e300"), None);
+        assert_eq!(parse_retry_after("NaN"), None);
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[test]
+    fn parses_retry_after_dates() {
+        let later = SystemTime::now() + Duration::from_secs(120);
+        let wait = parse_retry_after(&HttpDate::from(later).to_string()).unwrap();
+        assert!(wait > Duration::from_secs(100) && wait <= Duration::from_secs(120));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn caps_blocking() {
+        let limiter = RateLimiter::new(&ProviderSettings {
+            requests_per_minute: Some(10),
+            ..Default::default()
+        })
+        .unwrap();
+        limiter.block_for(Duration::MAX);
+
+        let mut state = limiter.state.lock().unwrap();
+        assert!(limiter.wait_time(&mut state, 0) <= MAX_BLOCK);
+    }
+}
@@ -0,0 +1,16 @@
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+
+/// Directory in which data about runs is kept, one subdirectory per run.
+pub fn runs_dir() -> anyhow::Result<PathBuf> {
+    Ok(dirs::data_dir()
+        .ok_or(anyhow!("Failed to locate appropriate data directory"))?
+        .join("minion")
+        .join("runs"))
+}
+
+/// Directory in which data about the run with the given ID is kept.
+pub fn run_dir(run_id: &str) -> anyhow::Result<PathBuf> {
+    Ok(runs_dir()?.join(run_id))
+}
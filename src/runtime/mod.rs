@@ -1,5 +1,6 @@
 use std::path::Path;
 use std::process::Command;
+use std::sync::Arc;
 
 use bollard::container::{
     AttachContainerOptions, Config, LogOutput, StartContainerOptions, WaitContainerOptions,
@@ -11,10 +12,16 @@ use futures::StreamExt;
 use futures::TryStreamExt;
 use uuid::Uuid;
 
+use crate::status::StatusLine;
+
 pub struct ContainerConfig {
     pub image: String,
     pub env_vars: Vec<(String, String)>,
     pub nested: bool,
+    /// Memory limit in bytes.
+    pub memory: Option<i64>,
+    /// Number of CPUs available to the container.
+    pub cpus: Option<f64>,
 }
 
 /// Runtime that uses the local Docker daemon to run containers.
@@ -147,7 +154,13 @@ impl LocalDockerRuntime {
     }
 
     /// Run a container with the given configuration.
-    pub async fn run_container(&self, config: ContainerConfig) -> anyhow::Result<String> {
+    /// Container output is written above the given status line.
+    pub async fn run_container(
+        &self,
+        config: ContainerConfig,
+        status: Arc<StatusLine>,
+        on_started: impl FnOnce(),
+    ) -> anyhow::Result<String> {
         let env: Vec<String> = config
             .env_vars
             .into_iter()
@@ -157,6 +170,8 @@ impl LocalDockerRuntime {
         let host_config = {
             let mut config_host = HostConfig {
                 extra_hosts: Some(vec!["host.docker.internal:host-gateway".to_string()]),
+                memory: config.memory,
+                nano_cpus: config.cpus.map(|cpus| (cpus * 1e9) as i64),
                 ..Default::default()
             };
 
@@ -191,6 +206,7 @@ impl LocalDockerRuntime {
         self.docker
             .start_container(&container.id, None::<StartContainerOptions<String>>)
             .await?;
+        on_started();
 
         let attach_options = Some(AttachContainerOptions::<&str> {
             stdout: Some(true),
@@ -214,12 +230,12 @@ impl LocalDockerRuntime {
                 match log {
                     LogOutput::StdOut { message } => {
                         if let Ok(text) = String::from_utf8(message.to_vec()) {
-                            print!("{text}");
+                            status.print(&text);
                         }
                     }
                     LogOutput::StdErr { message } => {
                         if let Ok(text) = String::from_utf8(message.to_vec()) {
-                            eprint!("{text}");
+                            status.eprint(&text);
                         }
                     }
                     _ => {}
@@ -0,0 +1,22 @@
+use anyhow::anyhow;
+
+use super::SecretStore;
+
+/// Reads secrets from environment variables. Secrets cannot be stored.
+pub struct Env;
+
+impl SecretStore for Env {
+    fn get(&self, name: &str) -> anyhow::Result<String> {
+        std::env::var(name).map_err(|_| anyhow!("Environment variable {name} is not set"))
+    }
+
+    fn set(&self, name: &str, _secret: &str) -> anyhow::Result<()> {
+        Err(anyhow!(
+            "Secrets cannot be stored in environment variables; set {name} yourself"
+        ))
+    }
+
+    fn delete(&self, _name: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+//! Storage for API keys and other secrets outside of the plaintext config file.
+//!
+//! The config file stores references of the form `<backend>:<name>` in place of secrets,
+//! e.g. `keyring:groq`, `keyring:profiles/work/groq` or `env:GROQ_API_KEY`. Values without a
+//! known backend prefix are treated as plaintext secrets for backwards compatibility.
+
+use serde::{Deserialize, Serialize};
+
+mod env;
+mod os_keyring;
+mod vault;
+
+/// A backend that secrets can be stored in and read from.
+pub trait SecretStore {
+    /// Read the secret with the given name.
+    fn get(&self, name: &str) -> anyhow::Result<String>;
+    /// Store a secret under the given name, replacing any existing secret.
+    fn set(&self, name: &str, secret: &str) -> anyhow::Result<()>;
+    /// Delete the secret with the given name.
+    fn delete(&self, name: &str) -> anyhow::Result<()>;
+}
+
+/// Where new secrets are stored when the config is saved.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SecretStorage {
+    /// The system keyring (Secret Service on Linux, Keychain on macOS, Credential Manager on Windows).
+    Keyring,
+    /// A passphrase-encrypted file next to the config file.
+    Vault,
+    /// Directly in the config file.
+    Plaintext,
+}
+
+/// A reference to a secret stored in one of the backends.
+pub struct SecretRef<'a> {
+    pub backend: &'a str,
+    pub name: &'a str,
+}
+
+impl<'a> SecretRef<'a> {
+    /// Parse a reference. Returns `None` for plaintext secrets.
+    pub fn parse(value: &'a str) -> Option<Self> {
+        let (backend, name) = value.split_once(':')?;
+        store(backend)?;
+        Some(SecretRef { backend, name })
+    }
+}
+
+fn store(backend: &str) -> Option<Box<dyn SecretStore>> {
+    match backend {
+        "keyring" => Some(Box::new(os_keyring::Keyring)),
+        "vault" => Some(Box::new(vault::Vault)),
+        "env" => Some(Box::new(env::Env)),
+        _ => None,
+    }
+}
+
+/// Resolve a config value to the secret it refers to.
+pub fn resolve(value: &str) -> anyhow::Result<String> {
+    match SecretRef::parse(value) {
+        Some(secret_ref) => store(secret_ref.backend)
+            .expect("Backend checked when parsing")
+            .get(secret_ref.name),
+        None => Ok(value.to_owned()),
+    }
+}
+
+/// Store a secret in the given backend and return the reference to put in the config.
+/// Plaintext storage returns the secret itself.
+pub fn store_secret(storage: SecretStorage, name: &str, secret: &str) -> anyhow::Result<String> {
+    let backend = match storage {
+        SecretStorage::Keyring => "keyring",
+        SecretStorage::Vault => "vault",
+        SecretStorage::Plaintext => return Ok(secret.to_owned()),
+    };
+    store(backend).expect("Known backend").set(name, secret)?;
+    Ok(format!("{backend}:{name}"))
+}
+
+/// Delete the secret a config value refers to. Does nothing for plaintext secrets.
+pub fn delete(value: &str) -> anyhow::Result<()> {
+    match SecretRef::parse(value) {
+        Some(secret_ref) => store(secret_ref.backend)
+            .expect("Backend checked when parsing")
+            .delete(secret_ref.name),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,25 @@
+use super::SecretStore;
+
+/// Service name under which secrets are stored in the system keyring.
+const SERVICE: &str = "minion";
+
+/// Stores secrets in the system keyring.
+pub struct Keyring;
+
+impl SecretStore for Keyring {
+    fn get(&self, name: &str) -> anyhow::Result<String> {
+        Ok(keyring::Entry::new(SERVICE, name)?.get_password()?)
+    }
+
+    fn set(&self, name: &str, secret: &str) -> anyhow::Result<()> {
+        keyring::Entry::new(SERVICE, name)?.set_password(secret)?;
+        Ok(())
+    }
+
+    fn delete(&self, name: &str) -> anyhow::Result<()> {
+        match keyring::Entry::new(SERVICE, name)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
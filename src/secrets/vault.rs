@@ -0,0 +1,112 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use age::secrecy::SecretString;
+use anyhow::anyhow;
+use once_cell::sync::Lazy;
+
+use super::SecretStore;
+use crate::config::Config;
+
+/// Environment variable from which the vault passphrase is read, if set.
+const PASSPHRASE_ENV_VAR: &str = "MINION_VAULT_PASSPHRASE";
+
+/// Passphrase and decrypted contents of the vault, kept for the lifetime of the process
+/// so that the user is only asked for the passphrase once.
+static UNLOCKED: Lazy<Mutex<Option<Unlocked>>> = Lazy::new(|| Mutex::new(None));
+
+struct Unlocked {
+    passphrase: SecretString,
+    secrets: BTreeMap<String, String>,
+}
+
+/// Stores secrets in a passphrase-encrypted file next to the config file.
+pub struct Vault;
+
+impl Vault {
+    fn filepath() -> anyhow::Result<PathBuf> {
+        Ok(Config::filepath()?.with_file_name("vault.age"))
+    }
+
+    /// Run `f` on the unlocked vault, asking for the passphrase if necessary.
+    fn with_unlocked<T>(f: impl FnOnce(&mut Unlocked) -> anyhow::Result<T>) -> anyhow::Result<T> {
+        let mut unlocked = UNLOCKED.lock().expect("Vault lock poisoned");
+        if unlocked.is_none() {
+            *unlocked = Some(Self::unlock()?);
+        }
+        f(unlocked.as_mut().expect("Vault unlocked above"))
+    }
+
+    fn unlock() -> anyhow::Result<Unlocked> {
+        let path = Self::filepath()?;
+        let exists = path.exists();
+        let passphrase = match std::env::var(PASSPHRASE_ENV_VAR) {
+            Ok(passphrase) => passphrase,
+            Err(_) if exists => rpassword::prompt_password("Vault passphrase: ")?,
+            Err(_) => {
+                let passphrase = rpassword::prompt_password("Choose a vault passphrase: ")?;
+                if passphrase != rpassword::prompt_password("Repeat the passphrase: ")? {
+                    return Err(anyhow!("Passphrases do not match"));
+                }
+                passphrase
+            }
+        };
+        let passphrase = SecretString::from(passphrase);
+
+        let secrets = if exists {
+            let identity = age::scrypt::Identity::new(passphrase.clone());
+            let plaintext = age::decrypt(&identity, &fs::read(&path)?)
+                .map_err(|err| anyhow!("Failed to unlock vault: {err}"))?;
+            serde_json::from_slice(&plaintext)?
+        } else {
+            BTreeMap::new()
+        };
+
+        Ok(Unlocked {
+            passphrase,
+            secrets,
+        })
+    }
+
+    fn save(unlocked: &Unlocked) -> anyhow::Result<()> {
+        let recipient = age::scrypt::Recipient::new(unlocked.passphrase.clone());
+        let plaintext = serde_json::to_vec(&unlocked.secrets)?;
+        let ciphertext = age::encrypt(&recipient, &plaintext)
+            .map_err(|err| anyhow!("Failed to encrypt vault: {err}"))?;
+
+        let path = Self::filepath()?;
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(path, ciphertext)?;
+        Ok(())
+    }
+}
+
+impl SecretStore for Vault {
+    fn get(&self, name: &str) -> anyhow::Result<String> {
+        Self::with_unlocked(|unlocked| {
+            unlocked
+                .secrets
+                .get(name)
+                .cloned()
+                .ok_or_else(|| anyhow!("Secret {name} not found in vault"))
+        })
+    }
+
+    fn set(&self, name: &str, secret: &str) -> anyhow::Result<()> {
+        Self::with_unlocked(|unlocked| {
+            unlocked.secrets.insert(name.to_owned(), secret.to_owned());
+            Self::save(unlocked)
+        })
+    }
+
+    fn delete(&self, name: &str) -> anyhow::Result<()> {
+        Self::with_unlocked(|unlocked| {
+            if unlocked.secrets.remove(name).is_some() {
+                Self::save(unlocked)?;
+            }
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,222 @@
+//! Live status area rendered below the scrolling container output.
+
+use std::io::{self, IsTerminal, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Progress information shown in the status line.
+#[derive(Default)]
+pub struct Progress {
+    /// The step the agent is currently working on.
+    pub step: Option<String>,
+    /// The agent's plan, if it reported one.
+    pub plan: Vec<String>,
+    /// Estimated overall progress in percent.
+    pub percentage: Option<f32>,
+    /// Total tokens used by the agent so far.
+    pub tokens: u64,
+    /// The most recent model call and when it was made.
+    pub last_model_call: Option<(String, Instant)>,
+    /// The provider whose rate limit requests are waiting for, and until when (if known).
+    pub rate_limited: Option<(String, Option<Instant>)>,
+}
+
+struct State {
+    progress: Progress,
+    /// Whether the status line is currently drawn on the terminal.
+    drawn: bool,
+    /// Whether the last output written did not end with a newline.
+    partial_line: bool,
+    /// Number of active suspensions, e.g. while the user is answering an inquiry.
+    suspended: usize,
+}
+
+/// A status line that stays at the bottom of the terminal while container output scrolls past.
+///
+/// All terminal output during a run should go through this type so that it can clear and
+/// redraw the status line around it. If stdout is not a terminal, the status line is never
+/// drawn and output is passed through unchanged.
+pub struct StatusLine {
+    enabled: bool,
+    started: Instant,
+    state: Mutex<State>,
+}
+
+impl Default for StatusLine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StatusLine {
+    pub fn new() -> Self {
+        Self {
+            enabled: io::stdout().is_terminal(),
+            started: Instant::now(),
+            state: Mutex::new(State {
+                progress: Progress::default(),
+                drawn: false,
+                partial_line: false,
+                suspended: 0,
+            }),
+        }
+    }
+
+    /// Update the progress information and redraw the status line.
+    pub fn update(&self, f: impl FnOnce(&mut Progress)) {
+        let mut state = self.state.lock().expect("Status lock poisoned");
+        f(&mut state.progress);
+        self.redraw(&mut state);
+    }
+
+    /// Write text to stdout above the status line.
+    pub fn print(&self, text: &str) {
+        self.write(text, false);
+    }
+
+    /// Write text to stderr above the status line.
+    pub fn eprint(&self, text: &str) {
+        self.write(text, true);
+    }
+
+    /// Hide the status line until the returned guard is dropped.
+    pub fn suspend(&self) -> Suspended<'_> {
+        let mut state = self.state.lock().expect("Status lock poisoned");
+        state.suspended += 1;
+        self.clear(&mut state);
+        Suspended { status: self }
+    }
+
+    /// Redraw the status line periodically so that the elapsed time stays current.
+    pub async fn tick(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+        loop {
+            interval.tick().await;
+            let mut state = self.state.lock().expect("Status lock poisoned");
+            self.redraw(&mut state);
+        }
+    }
+
+    /// Remove the status line from the terminal for good.
+    pub fn finish(&self) {
+        let mut state = self.state.lock().expect("Status lock poisoned");
+        state.suspended += 1;
+        self.clear(&mut state);
+    }
+
+    fn write(&self, text: &str, stderr: bool) {
+        let mut state = self.state.lock().expect("Status lock poisoned");
+        self.clear(&mut state);
+        if stderr {
+            eprint!("{text}");
+            io::stderr().flush().ok();
+        } else {
+            print!("{text}");
+            io::stdout().flush().ok();
+        }
+        if !text.is_empty() {
+            state.partial_line = !text.ends_with('\n');
+        }
+        self.redraw(&mut state);
+    }
+
+    fn clear(&self, state: &mut State) {
+        if state.drawn {
+            print!("\r\x1b[2K");
+            io::stdout().flush().ok();
+            state.drawn = false;
+        }
+    }
+
+    fn redraw(&self, state: &mut State) {
+        if !self.enabled || state.suspended > 0 || state.partial_line {
+            return;
+        }
+        self.clear(state);
+        print!("\x1b[7m{}\x1b[0m", self.render(&state.progress));
+        io::stdout().flush().ok();
+        state.drawn = true;
+    }
+
+    fn render(&self, progress: &Progress) -> String {
+        let mut parts = vec![format_duration(self.started.elapsed())];
+
+        if let Some(percentage) = progress.percentage {
+            parts.push(format!("{percentage:.0}%"));
+        }
+        if let Some(step) = &progress.step {
+            match progress.plan.iter().position(|s| s == step) {
+                Some(i) => parts.push(format!("step {}/{}: {step}", i + 1, progress.plan.len())),
+                None => parts.push(step.clone()),
+            }
+        }
+        parts.push(format!("{} tokens", format_count(progress.tokens)));
+        if let Some((model, at)) = &progress.last_model_call {
+            parts.push(format!(
+                "last call: {model} {} ago",
+                format_duration(at.elapsed())
+            ));
+        }
+        match &progress.rate_limited {
+            Some((provider, Some(until))) => parts.push(format!(
+                "waiting {}s for {provider} rate limit",
+                until.saturating_duration_since(Instant::now()).as_secs()
+            )),
+            Some((provider, None)) => parts.push(format!("waiting for {provider} rate limit")),
+            None => {}
+        }
+
+        let line = format!(" {} ", parts.join(" | "));
+        truncate_to_width(&line, terminal_width())
+    }
+}
+
+/// Guard returned by [`StatusLine::suspend`].
+pub struct Suspended<'a> {
+    status: &'a StatusLine,
+}
+
+impl Drop for Suspended<'_> {
+    fn drop(&mut self) {
+        let mut state = self.status.state.lock().expect("Status lock poisoned");
+        state.suspended -= 1;
+        // Anything typed by the user ends with a newline.
+        state.partial_line = false;
+        self.status.redraw(&mut state);
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{:02}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+/// A count in compact form, e.g. `12.3k`.
+pub fn format_count(count: u64) -> String {
+    match count {
+        0..1_000 => count.to_string(),
+        1_000..1_000_000 => format!("{:.1}k", count as f64 / 1_000.0),
+        _ => format!("{:.1}M", count as f64 / 1_000_000.0),
+    }
+}
+
+/// Terminal width taken from `$COLUMNS`, defaulting to 80.
+fn terminal_width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .unwrap_or(80)
+}
+
+fn truncate_to_width(line: &str, width: usize) -> String {
+    if line.chars().count() <= width {
+        return line.to_owned();
+    }
+    let mut truncated: String = line.chars().take(width.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
@@ -0,0 +1,211 @@
+//! LLM usage of runs: requests, tokens, latency and estimated cost per model.
+//!
+//! The usage of each run is stored in its run record, so that totals over several runs can
+//! be reported with `minion usage`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::providers::ModelInfo;
+
+/// Name of the file in a run's directory holding its usage.
+const USAGE_FILENAME: &str = "usage.json";
+
+/// Usage of a single model.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ModelUsage {
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Time until the responses started, summed over all requests.
+    pub latency_secs: f64,
+    /// Estimated cost in USD, if the model's prices are known.
+    pub cost: Option<f64>,
+    /// Whether some of the usage couldn't be priced, in which case `cost` is a lower bound.
+    #[serde(default)]
+    pub partial_cost: bool,
+}
+
+impl ModelUsage {
+    /// Add the usage of another run or model.
+    pub fn add(&mut self, other: &ModelUsage) {
+        self.requests += other.requests;
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.latency_secs += other.latency_secs;
+        let priced = self.cost.is_some() || other.cost.is_some();
+        self.partial_cost |= other.partial_cost || priced && (self.unpriced() || other.unpriced());
+        self.cost = match (self.cost, other.cost) {
+            (Some(a), Some(b)) => Some(a + b),
+            (a, b) => a.or(b),
+        };
+    }
+
+    /// Whether there is usage, but no cost for it.
+    fn unpriced(&self) -> bool {
+        self.cost.is_none()
+            && (self.requests > 0 || self.prompt_tokens > 0 || self.completion_tokens > 0)
+    }
+
+    /// Average time until the responses started.
+    pub fn average_latency(&self) -> Option<Duration> {
+        (self.requests > 0)
+            .then(|| Duration::from_secs_f64(self.latency_secs / self.requests as f64))
+    }
+}
+
+/// Usage of a run, as stored in its run record.
+#[derive(Default, Serialize, Deserialize)]
+pub struct RunUsage {
+    /// Seconds since the Unix epoch at which the run started.
+    pub started_at: u64,
+    /// Usage by upstream model (`provider/model`).
+    pub models: BTreeMap<String, ModelUsage>,
+}
+
+impl RunUsage {
+    /// Write the usage to the given run directory.
+    pub fn save(&self, run_dir: &Path) -> anyhow::Result<()> {
+        fs::create_dir_all(run_dir)?;
+        fs::write(
+            run_dir.join(USAGE_FILENAME),
+            serde_json::to_vec_pretty(self)?,
+        )?;
+        Ok(())
+    }
+
+    /// The usage of all recorded runs that started at or after `since` (seconds since the
+    /// Unix epoch). Runs without a readable usage record are skipped.
+    pub fn load_since(since: u64) -> anyhow::Result<Vec<RunUsage>> {
+        let runs_dir = crate::runs::runs_dir()?;
+        if !runs_dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut runs = Vec::new();
+        for entry in fs::read_dir(runs_dir)? {
+            let path = entry?.path().join(USAGE_FILENAME);
+            let Ok(data) = fs::read(&path) else {
+                continue;
+            };
+            match serde_json::from_slice::<RunUsage>(&data) {
+                Ok(usage) if usage.started_at >= since => runs.push(usage),
+                Ok(_) => {}
+                Err(err) => log::warn!("Ignoring {}: {err}", path.to_string_lossy()),
+            }
+        }
+        Ok(runs)
+    }
+
+    /// Add up the usage of several runs by model.
+    pub fn total<'a>(runs: impl IntoIterator<Item = &'a RunUsage>) -> BTreeMap<String, ModelUsage> {
+        let mut total = BTreeMap::<String, ModelUsage>::new();
+        for run in runs {
+            for (model, usage) in &run.models {
+                total.entry(model.clone()).or_default().add(usage);
+            }
+        }
+        total
+    }
+}
+
+/// Accumulates the usage of a run while the agent makes requests.
+pub struct UsageTracker {
+    started_at: u64,
+    /// Prompt and completion prices in USD per token, by upstream model.
+    prices: HashMap<String, (f64, f64)>,
+    models: Mutex<BTreeMap<String, ModelUsage>>,
+}
+
+impl UsageTracker {
+    /// Create a tracker that estimates costs with the prices from the model catalog.
+    pub fn new(catalog: &HashMap<String, ModelInfo>) -> Self {
+        let prices = catalog
+            .iter()
+            .filter_map(|(model, info)| {
+                Some((model.clone(), (info.prompt_price?, info.completion_price?)))
+            })
+            .collect();
+        Self {
+            started_at: now(),
+            prices,
+            models: Mutex::default(),
+        }
+    }
+
+    /// Count a request to the upstream model and the time until its response started.
+    pub fn record_request(&self, upstream_model: &str, latency: Duration) {
+        let mut models = self.models.lock().expect("Usage lock poisoned");
+        let usage = models.entry(upstream_model.to_owned()).or_default();
+        usage.requests += 1;
+        usage.latency_secs += latency.as_secs_f64();
+    }
+
+    /// Count the tokens of a completed interaction with the upstream model.
+    pub fn record_tokens(&self, upstream_model: &str, prompt_tokens: u64, completion_tokens: u64) {
+        let mut models = self.models.lock().expect("Usage lock poisoned");
+        let usage = models.entry(upstream_model.to_owned()).or_default();
+        usage.prompt_tokens += prompt_tokens;
+        usage.completion_tokens += completion_tokens;
+        if let Some((prompt_price, completion_price)) = self.prices.get(upstream_model) {
+            let cost =
+                prompt_tokens as f64 * prompt_price + completion_tokens as f64 * completion_price;
+            usage.cost = Some(usage.cost.unwrap_or(0.0) + cost);
+        }
+    }
+
+    /// The usage recorded so far.
+    pub fn snapshot(&self) -> RunUsage {
+        RunUsage {
+            started_at: self.started_at,
+            models: self.models.lock().expect("Usage lock poisoned").clone(),
+        }
+    }
+}
+
+/// Seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(cost: Option<f64>) -> ModelUsage {
+        ModelUsage {
+            requests: 1,
+            prompt_tokens: 100,
+            completion_tokens: 10,
+            latency_secs: 1.0,
+            cost,
+            partial_cost: false,
+        }
+    }
+
+    #[test]
+    fn adding_unpriced_usage_makes_cost_a_lower_bound() {
+        let mut total = ModelUsage::default();
+        total.add(&usage(None));
+        assert_eq!(total.cost, None);
+        assert!(!total.partial_cost);
+
+        total.add(&usage(Some(0.5)));
+        assert_eq!(total.cost, Some(0.5));
+        assert!(total.partial_cost);
+
+        let mut priced = usage(Some(0.25));
+        priced.add(&usage(Some(0.5)));
+        assert_eq!(priced.cost, Some(0.75));
+        assert!(!priced.partial_cost);
+        priced.add(&total);
+        assert!(priced.partial_cost);
+    }
+}
@@ -1,7 +1,53 @@
 use std::net::TcpListener;
 
+use crate::prompt;
+
 /// Binds to "127.0.0.1:0" to let the OS assign an available port,
 /// then returns the listener.
 pub fn listen_to_free_port(host: &str) -> TcpListener {
     TcpListener::bind(format!("{host}:0")).expect("Could not bind to a free port")
 }
+
+/// Mask a secret for display, keeping only a few characters at the start and end.
+pub fn mask_secret(secret: &str) -> String {
+    let chars: Vec<char> = secret.chars().collect();
+    if chars.len() <= 12 {
+        return "*".repeat(chars.len());
+    }
+    let start: String = chars[..4].iter().collect();
+    let end: String = chars[chars.len() - 4..].iter().collect();
+    format!("{start}…{end}")
+}
+
+/// Open a URL in the user's web browser. On systems without a graphical display, e.g.
+/// headless servers, or if the browser fails to open, the user is asked to open the URL
+/// manually instead. Returns whether the browser was opened.
+pub fn open_browser(url: &str) -> bool {
+    if has_display() {
+        match webbrowser::open(url) {
+            Ok(()) => return true,
+            Err(err) => eprintln!("Failed to open browser: {err}"),
+        }
+    }
+    println!("Please open this URL manually: {url}");
+    false
+}
+
+/// Whether a graphical display is likely available to open a browser on.
+fn has_display() -> bool {
+    if cfg!(any(target_os = "windows", target_os = "macos")) {
+        return true;
+    }
+    ["DISPLAY", "WAYLAND_DISPLAY"]
+        .iter()
+        .any(|var| std::env::var_os(var).is_some_and(|v| !v.is_empty()))
+}
+
+/// Ask the user to enter an API key.
+pub fn read_api_key(provider_name: &str) -> anyhow::Result<String> {
+    let input = prompt::read_line(&format!("Please enter your {provider_name} API key: "))?;
+    if input.is_empty() {
+        return Err(anyhow::anyhow!("No API key provided."));
+    }
+    Ok(input)
+}